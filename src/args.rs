@@ -1,22 +1,41 @@
 use std::{ffi::OsString, path::PathBuf};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
-#[derive(Parser, PartialEq, Eq, Debug)]
+use crate::config::{Engine, IdType, IndexType, OutputFormat, Topology, TpcbMode};
+
+#[derive(Parser, PartialEq, Debug)]
 #[command(version, about, long_about = "")]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
     #[arg(short = 'v', action = clap::ArgAction::Count, global = true, default_value_t = 0)]
     pub verbosity: u8,
-    #[arg(long = "config", global = true)]
+    #[arg(
+        long = "config",
+        help = "Path to a TOML file describing instances to connect to, \
+            bucket_count, max_tries, fillfactor and scale. Command line \
+            options take precedence over values from the file.",
+        global = true
+    )]
     pub config: Option<PathBuf>,
     #[arg(
-        short = 'i', 
+        short = 'i',
         long = "initialize",
         action = clap::ArgAction::SetTrue,
-        help = "Required to invoke initialization mode.",
+        help = "Initialize the benchmark spaces as init does before the run.",
         global = true
     )]
     pub init: bool,
+    #[arg(
+        long = "check",
+        action = clap::ArgAction::SetTrue,
+        help = "Check after the run that balance sums match history deltas \
+            and that the history rows added match the transactions. Exit \
+            with an error on a mismatch.",
+        global = true
+    )]
+    pub check: bool,
     #[arg(
         short = 't',
         long = "transactions",
@@ -30,32 +49,121 @@ pub struct Cli {
         help = "Run the test for this many seconds, rather than a fixed number \
             of transactions per client. -t and -T are mutually exclusive.",
         global = true,
-        default_value_t = 1
+        default_value_t = 60
     )]
     pub time: u64,
+    #[arg(
+        short = 'P',
+        long = "progress",
+        help = "Show progress report every sec seconds. The report includes \
+            the time since the beginning of the run, the TPS since the last \
+            report, and the transaction latency average, standard deviation \
+            and the number of failed transactions since the last report.",
+        value_name = "sec",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    pub progress: Option<u64>,
+    #[arg(
+        short = 'R',
+        long = "rate",
+        help = "Execute transactions targeting the specified rate instead of \
+            running as fast as possible. The rate is shared by all jobs, \
+            transaction start times are scheduled on a Poisson process and \
+            the lag behind the schedule is reported separately from latency.",
+        value_name = "tps",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    pub rate: Option<u64>,
+    #[arg(
+        short = 'L',
+        long = "latency-limit",
+        help = "Skip transactions which would start more than limit \
+            milliseconds behind their schedule. Skipped transactions are \
+            counted separately. Requires --rate.",
+        value_name = "limit",
+        requires = "rate",
+        global = true
+    )]
+    pub latency_limit: Option<u64>,
+    #[arg(
+        short = 'b',
+        long = "builtin",
+        help = "Add the specified built-in script to the list of scripts to be \
+            executed. Available built-in scripts are: tpcb-like, simple-update \
+            and select-only. An optional integer weight after @ allows to \
+            adjust the probability of selecting this script. The default \
+            weight is 1. Default is tpcb-like unless -f is given.",
+        value_name = "scriptname[@weight]",
+        global = true
+    )]
+    pub builtins: Vec<String>,
+    #[arg(
+        short = 'f',
+        long = "file",
+        help = "Add a transaction script read from filename to the list of \
+            scripts to be executed. An optional integer weight after @ allows \
+            to adjust the probability of selecting this script. The default \
+            weight is 1. The script format is described in README.md.",
+        value_name = "filename[@weight]",
+        global = true
+    )]
+    pub files: Vec<String>,
     #[arg(
         short = 'S',
         long = "scale",
         help = "Multiply the number of tuples generated by the scale factor. \
-            For example, -s 100 will create 10,000,000 tuples in the 
+            For example, -s 100 will create 10,000,000 tuples in the \
             ttbench_accounts table. Default is 1. When the scale is 20,000 or \
-            larger, the columns used to hold account identifiers (aid columns) \
-            will switch to using larger integers (bigint), \
-            in order to be big enough to hold the range of account identifiers.",
-        default_value_t = 1
-    )]
-    pub scale: u64,
-    // TODO Help
-    #[arg(short = 'I', long = "init-steps")]
+            larger, the id columns switch from unsigned to integer, see \
+            --id-type.",
+        global = true
+    )]
+    pub scale: Option<u64>,
+    #[arg(
+        short = 'I',
+        long = "init-steps",
+        help = "Perform just a selected set of the normal initialization \
+            steps in the given order. Each step is a letter: d (drop), \
+            t (create spaces), p (primary indexes), f (secondary indexes), \
+            g (client-side data generation), G (server-side data generation, \
+            every storage generates its own tuples) and v (vacuum). Primary \
+            indexes are required before the data is generated, secondary \
+            ones are built faster after it. Default is dtpgfv.",
+        value_name = "init_steps",
+        global = true
+    )]
     pub init_steps: Option<String>,
+    #[arg(
+        long = "init-batch-size",
+        help = "Number of tuples sent in one request by the client-side data \
+            generation. Tuples of a batch are grouped by replicaset and \
+            inserted in one transaction on each. Default is 1000.",
+        value_name = "size",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value_t = 1000,
+        global = true
+    )]
+    pub init_batch_size: u64,
+    #[arg(
+        long = "vacuum-timeout",
+        help = "Seconds the vacuum init step waits for the vinyl compaction \
+            before it fails. Default is 600.",
+        value_name = "seconds",
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value_t = 600,
+        global = true
+    )]
+    pub vacuum_timeout: u64,
     #[arg(
         short = 'c',
         long = "connections",
-        help = "Number of connections openned, that is, number of concurrent \
-            IPROTO sessions. Default is 2.",
-        default_value_t = 2
+        help = "Number of connections openned to each instance, that is, number \
+            of concurrent IPROTO sessions. Default is 3.",
+        global = true
     )]
-    pub connections: u64,
+    pub connections: Option<u64>,
     #[arg(
         short = 'j',
         long = "jobs",
@@ -63,6 +171,7 @@ pub struct Cli {
             thread can be helpful on multi-CPU machines. Connections are distributed \
             as evenly as possible among available jobs. Default is 100.",
         default_value_t = 100,
+        global = true
     )]
     pub jobs: u64,
     #[arg(
@@ -72,6 +181,197 @@ pub struct Cli {
         global = true,
     )]
     pub keep_history: bool,
+    #[arg(
+        long = "tpcb-mode",
+        help = "Where the tpcb-like transaction is executed. With client every \
+            statement is a separate router request. With server the create \
+            step installs the tpcb_like procedure on storages and every \
+            transaction is a single call of it. Server mode shards tellers, \
+            accounts and history by branch, so use the same mode for \
+            initialization and the run.",
+        value_enum,
+        default_value_t = TpcbMode::Client,
+        global = true
+    )]
+    pub tpcb_mode: TpcbMode,
+    #[arg(
+        long = "topology",
+        help = "Kind of the tested installation. With vshard every request \
+            goes through vshard.router of a Cartridge cluster. With box a \
+            standalone instance or a single replicaset is tested: spaces are \
+            created with box.schema, data is accessed with native requests \
+            and there is no bucket_id field.",
+        value_enum,
+        default_value_t = Topology::Vshard,
+        global = true
+    )]
+    pub topology: Topology,
+    #[arg(
+        long = "engine",
+        help = "Storage engine of the benchmark spaces.",
+        value_enum,
+        default_value_t = Engine::Memtx,
+        global = true
+    )]
+    pub engine: Engine,
+    #[arg(
+        long = "primary-index",
+        help = "Type of primary indexes. Default is hash for memtx and tree \
+            for vinyl, which supports tree indexes only.",
+        value_enum,
+        global = true
+    )]
+    pub primary_index: Option<IndexType>,
+    #[arg(
+        long = "vinyl-page-size",
+        help = "Page size of vinyl indexes in bytes.",
+        value_name = "bytes",
+        global = true
+    )]
+    pub vinyl_page_size: Option<u64>,
+    #[arg(
+        long = "vinyl-run-count-per-level",
+        help = "Maximum number of runs per level of vinyl indexes.",
+        value_name = "count",
+        global = true
+    )]
+    pub vinyl_run_count_per_level: Option<u64>,
+    #[arg(
+        long = "vinyl-bloom-fpr",
+        help = "Bloom filter false positive rate of vinyl indexes.",
+        value_name = "rate",
+        global = true
+    )]
+    pub vinyl_bloom_fpr: Option<f64>,
+    #[arg(
+        long = "fillfactor",
+        help = "Percentage of vinyl pages and ranges to fill, from 10 to 100. \
+            Below 100 the instance default page_size and range_size are \
+            scaled down, so updates rewrite less data. Memtx ignores it. \
+            Default is 100.",
+        value_parser = clap::value_parser!(u64).range(10..=100),
+        global = true
+    )]
+    pub fillfactor: Option<u64>,
+    #[arg(
+        long = "id-type",
+        help = "Field type of aid, tid and bid columns. Default is unsigned, \
+            or integer when the scale is 20,000 or larger.",
+        value_enum,
+        global = true
+    )]
+    pub id_type: Option<IdType>,
+    #[arg(
+        long = "delta-min",
+        help = "Smallest balance change of the tpcb-like and simple-update \
+            transactions. Default is -5000.",
+        default_value_t = -5000,
+        allow_negative_numbers = true,
+        global = true
+    )]
+    pub delta_min: i64,
+    #[arg(
+        long = "delta-max",
+        help = "Largest balance change of the tpcb-like and simple-update \
+            transactions. Default is 5000.",
+        default_value_t = 5000,
+        allow_negative_numbers = true,
+        global = true
+    )]
+    pub delta_max: i64,
+    #[arg(
+        long = "max-tries",
+        help = "Maximum number of tries of a transaction failed with a \
+            transaction conflict or a bucket being moved. It is retried with \
+            the same values. Other errors are never retried. Default is 1, \
+            no retries.",
+        value_parser = clap::value_parser!(u64).range(1..),
+        global = true
+    )]
+    pub max_tries: Option<u64>,
+    #[arg(
+        long = "failures-detailed",
+        action = clap::ArgAction::SetTrue,
+        help = "Break failed transactions down by box error code and vshard \
+            error name in the report.",
+        global = true
+    )]
+    pub failures_detailed: bool,
+    #[arg(
+        long = "verbose-errors",
+        action = clap::ArgAction::SetTrue,
+        help = "Print each error with the job, the transaction values and the \
+            index of the failed statement, begin and commit included.",
+        global = true
+    )]
+    pub verbose_errors: bool,
+    #[arg(
+        short = 'r',
+        long = "report-per-statement",
+        action = clap::ArgAction::SetTrue,
+        help = "Report the average latency and the number of failures of \
+            every statement of every transaction type after the run.",
+        global = true
+    )]
+    pub report_per_statement: bool,
+    #[arg(
+        short = 'l',
+        long = "log",
+        action = clap::ArgAction::SetTrue,
+        help = "Write a line about each transaction to a log file per job.",
+        global = true
+    )]
+    pub log: bool,
+    #[arg(
+        long = "log-prefix",
+        help = "Prefix of the log file names, which are \
+            <prefix>.<pid>.<job id>. Default is ttbench_log.",
+        requires = "log",
+        global = true
+    )]
+    pub log_prefix: Option<String>,
+    #[arg(
+        long = "sampling-rate",
+        help = "Fraction of transactions written to the log, e.g. 0.01.",
+        value_name = "rate",
+        requires = "log",
+        conflicts_with = "aggregate_interval",
+        global = true
+    )]
+    pub sampling_rate: Option<f64>,
+    #[arg(
+        long = "aggregate-interval",
+        help = "Write the count, sum, sum of squares, min and max of \
+            latencies of each interval to the log instead of transactions.",
+        value_name = "seconds",
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "log",
+        global = true
+    )]
+    pub aggregate_interval: Option<u64>,
+    #[arg(
+        long = "output",
+        help = "Format of the result. With json or csv a result document is \
+            printed to stdout after the run or the initialization, and the \
+            other messages go to stderr.",
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        global = true
+    )]
+    pub output: OutputFormat,
+}
+
+#[derive(Subcommand, PartialEq, Eq, Debug)]
+pub enum Command {
+    /// Create and fill the benchmark spaces according to --init-steps.
+    Init,
+    /// Run the benchmark against initialized spaces.
+    Run,
+    /// Drop the benchmark spaces.
+    Cleanup,
+    /// Check connectivity, the benchmark spaces and their sizes for the scale,
+    /// and that balance sums match history deltas.
+    Check,
 }
 
 pub fn init<A, T>(args: A) -> Cli
@@ -88,7 +388,9 @@ mod test {
 
     use clap::Parser;
 
-    use super::{init, Cli};
+    use crate::config::{Engine, IndexType, OutputFormat, Topology, TpcbMode};
+
+    use super::{init, Cli, Command};
 
     #[test]
     fn test_run_cmd() {
@@ -97,16 +399,45 @@ mod test {
         assert_eq!(
             args,
             Cli {
-                    scale: 1,
-                    init_steps: None,
-                    connections: 2,
-                    jobs: 100,
+                command: Command::Run,
+                scale: None,
+                init_steps: None,
+                init_batch_size: 1000,
+                vacuum_timeout: 600,
+                engine: Engine::Memtx,
+                primary_index: None,
+                vinyl_page_size: None,
+                vinyl_run_count_per_level: None,
+                vinyl_bloom_fpr: None,
+                fillfactor: None,
+                id_type: None,
+                delta_min: -5000,
+                delta_max: 5000,
+                max_tries: None,
+                failures_detailed: false,
+                verbose_errors: false,
+                report_per_statement: false,
+                log: false,
+                log_prefix: None,
+                sampling_rate: None,
+                aggregate_interval: None,
+                output: OutputFormat::Text,
+                connections: None,
+                jobs: 100,
                 verbosity: 4,
                 config: None,
                 init: false,
+                check: false,
                 transactions: None,
                 time: 60,
-                keep_history: false
+                progress: None,
+                rate: None,
+                latency_limit: None,
+                builtins: vec![],
+                files: vec![],
+                keep_history: false,
+                tpcb_mode: TpcbMode::Client,
+                topology: Topology::Vshard,
             }
         );
 
@@ -122,7 +453,7 @@ mod test {
             "dtpGv",
             "-i",
             "--keep-history",
-            "--c",
+            "-c",
             "10",
             "-j",
             "1000",
@@ -131,16 +462,45 @@ mod test {
         assert_eq!(
             args,
             Cli {
-                    scale: 200,
-                    init_steps: Some("dtpGv".into()),
-                    connections: 2,
-                    jobs: 1000,
+                command: Command::Run,
+                scale: Some(200),
+                init_steps: Some("dtpGv".into()),
+                init_batch_size: 1000,
+                vacuum_timeout: 600,
+                engine: Engine::Memtx,
+                primary_index: None,
+                vinyl_page_size: None,
+                vinyl_run_count_per_level: None,
+                vinyl_bloom_fpr: None,
+                fillfactor: None,
+                id_type: None,
+                delta_min: -5000,
+                delta_max: 5000,
+                max_tries: None,
+                failures_detailed: false,
+                verbose_errors: false,
+                report_per_statement: false,
+                log: false,
+                log_prefix: None,
+                sampling_rate: None,
+                aggregate_interval: None,
+                output: OutputFormat::Text,
+                connections: Some(10),
+                jobs: 1000,
                 verbosity: 4,
                 config: Some(PathBuf::from_str("/tmp/config.toml").unwrap()),
                 init: true,
+                check: false,
                 transactions: None,
                 time: 60,
-                keep_history: false
+                progress: None,
+                rate: None,
+                latency_limit: None,
+                builtins: vec![],
+                files: vec![],
+                keep_history: true,
+                tpcb_mode: TpcbMode::Client,
+                topology: Topology::Vshard,
             }
         );
 
@@ -149,16 +509,45 @@ mod test {
         assert_eq!(
             args,
             Cli {
-                    scale: 1,
-                    init_steps: None,
-                    connections: 2,
-                    jobs: 100,
+                command: Command::Run,
+                scale: None,
+                init_steps: None,
+                init_batch_size: 1000,
+                vacuum_timeout: 600,
+                engine: Engine::Memtx,
+                primary_index: None,
+                vinyl_page_size: None,
+                vinyl_run_count_per_level: None,
+                vinyl_bloom_fpr: None,
+                fillfactor: None,
+                id_type: None,
+                delta_min: -5000,
+                delta_max: 5000,
+                max_tries: None,
+                failures_detailed: false,
+                verbose_errors: false,
+                report_per_statement: false,
+                log: false,
+                log_prefix: None,
+                sampling_rate: None,
+                aggregate_interval: None,
+                output: OutputFormat::Text,
+                connections: None,
+                jobs: 100,
                 verbosity: 0,
                 config: None,
                 init: false,
+                check: false,
                 transactions: None,
                 time: 60,
-                keep_history: false
+                progress: None,
+                rate: None,
+                latency_limit: None,
+                builtins: vec![],
+                files: vec![],
+                keep_history: false,
+                tpcb_mode: TpcbMode::Client,
+                topology: Topology::Vshard,
             }
         );
     }
@@ -170,17 +559,85 @@ mod test {
         assert_eq!(
             cli,
             Cli {
-                    scale: 1,
-                    init_steps: None,
-                    connections: 2,
-                    jobs: 100,
+                command: Command::Run,
+                scale: None,
+                init_steps: None,
+                init_batch_size: 1000,
+                vacuum_timeout: 600,
+                engine: Engine::Memtx,
+                primary_index: None,
+                vinyl_page_size: None,
+                vinyl_run_count_per_level: None,
+                vinyl_bloom_fpr: None,
+                fillfactor: None,
+                id_type: None,
+                delta_min: -5000,
+                delta_max: 5000,
+                max_tries: None,
+                failures_detailed: false,
+                verbose_errors: false,
+                report_per_statement: false,
+                log: false,
+                log_prefix: None,
+                sampling_rate: None,
+                aggregate_interval: None,
+                output: OutputFormat::Text,
+                connections: None,
+                jobs: 100,
                 verbosity: 4,
                 config: None,
                 init: false,
+                check: false,
                 transactions: None,
                 time: 60,
-                keep_history: false
+                progress: None,
+                rate: None,
+                latency_limit: None,
+                builtins: vec![],
+                files: vec![],
+                keep_history: false,
+                tpcb_mode: TpcbMode::Client,
+                topology: Topology::Vshard,
             }
         );
+
+        assert_eq!(
+            init(["ttbench", "init", "-S", "500"]).command,
+            Command::Init
+        );
+        assert_eq!(init(["ttbench", "cleanup"]).command, Command::Cleanup);
+        assert_eq!(init(["ttbench", "-v", "check"]).command, Command::Check);
+
+        let cli = init([
+            "ttbench",
+            "init",
+            "--engine",
+            "vinyl",
+            "--primary-index",
+            "tree",
+            "--vinyl-page-size",
+            "16384",
+        ]);
+        assert_eq!(cli.engine, Engine::Vinyl);
+        assert_eq!(cli.primary_index, Some(IndexType::Tree));
+        assert_eq!(cli.vinyl_page_size, Some(16384));
+
+        let cli = init(["ttbench", "run", "--delta-min", "-10", "--delta-max", "-1"]);
+        assert_eq!((cli.delta_min, cli.delta_max), (-10, -1));
+
+        let cli = init(["ttbench", "run", "-l", "--aggregate-interval", "5"]);
+        assert!(cli.log);
+        assert_eq!(cli.aggregate_interval, Some(5));
+        assert!(Cli::try_parse_from(["ttbench", "run", "--sampling-rate", "0.1"]).is_err());
+        assert!(Cli::try_parse_from([
+            "ttbench",
+            "run",
+            "-l",
+            "--sampling-rate",
+            "0.1",
+            "--aggregate-interval",
+            "5"
+        ])
+        .is_err());
     }
 }
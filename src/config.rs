@@ -1,10 +1,30 @@
-use std::{collections::HashSet, fmt::Display, time::Duration};
+use std::{fmt::Display, path::Path, sync::Arc, time::Duration};
 
 use anyhow::anyhow;
 
-use crate::args::Cli;
+use crate::{
+    args::Cli,
+    job::workload::{Builtin, Workload, WorkloadKind},
+    script::Script,
+    utils::{calculate_bucket_id, message},
+};
 
-const DEFAULT_STEPS: &str = "dtpfvg";
+use self::file::ConfigFile;
+
+mod file;
+
+const DEFAULT_STEPS: &str = "dtpgfv";
+const DEFAULT_SCALE: u64 = 1;
+const DEFAULT_BUCKET_COUNT: u32 = 30000;
+const DEFAULT_FILLFACTOR: u64 = 100;
+/// Defaults of `vinyl_page_size` and the upper bound of the automatically
+/// chosen `vinyl_range_size`, which the fillfactor is applied to.
+const VINYL_PAGE_SIZE: u64 = 8192;
+const VINYL_RANGE_SIZE: u64 = 1 << 30;
+/// Largest integer a double, and so a Lua number, holds exactly.
+const MAX_EXACT_NUMBER: u64 = 1 << 53;
+pub const TELLERS_PER_BRANCH: u64 = 10;
+pub const ACCOUNTS_PER_BRANCH: u64 = 100_000;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -13,50 +33,326 @@ pub struct Config {
     pub fillfactor: u64,
     pub jobs: u64,
     pub time: Duration,
-    pub vebose_errors: bool,
+    /// interval between progress reports
+    pub progress: Option<Duration>,
+    /// target transactions per second
+    pub rate: Option<f64>,
+    pub latency_limit: Option<Duration>,
+    pub verbose_errors: bool,
+    /// break failures down by error code in the report
+    pub failures_detailed: bool,
+    /// time every statement of the transactions
+    pub report_per_statement: bool,
+    /// per-transaction log of the run
+    pub log: Option<LogOptions>,
     // transactions per connection
     pub transactions: u64,
     // transactions summary for all connections
     pub transactions_total: u64,
     pub instances: Vec<ConnectionConfig>,
     pub test_config: TestConfig,
-    pub max_retries: u64,
+    pub workloads: Vec<Workload>,
+    /// tries of a transaction failed with a retryable error
+    pub max_tries: u64,
     pub bucket_count: u32,
     pub keep_history: bool,
+    pub tpcb_mode: TpcbMode,
+    pub topology: Topology,
+    /// tuples sent in one request by the client-side data generation
+    pub init_batch_size: u64,
+    /// wait for the vinyl compaction of the vacuum step
+    pub vacuum_timeout: Duration,
+    pub engine: Engine,
+    pub primary_index: IndexType,
+    pub vinyl: VinylOptions,
+    pub id_type: IdType,
+    /// range of the tpcb-like balance changes
+    pub delta_min: i64,
+    pub delta_max: i64,
+    /// check consistency of balances and history after the run
+    pub check: bool,
+    pub output: OutputFormat,
 }
 
 impl Config {
-    pub fn contains_step(&self, step: &InitStep) -> bool {
-        self.init_steps.0.contains(step)
+    pub fn branch_bucket_id(&self, bid: u64) -> u32 {
+        calculate_bucket_id(bid.to_string(), self.bucket_count)
+    }
+
+    /// In the server mode tellers are stored together with their branch.
+    pub fn teller_bucket_id(&self, tid: u64) -> u32 {
+        match self.tpcb_mode {
+            TpcbMode::Client => calculate_bucket_id(tid.to_string(), self.bucket_count),
+            TpcbMode::Server => self.branch_bucket_id(tid / TELLERS_PER_BRANCH),
+        }
+    }
+
+    /// In the server mode accounts are stored together with their branch.
+    pub fn account_bucket_id(&self, aid: u64) -> u32 {
+        match self.tpcb_mode {
+            TpcbMode::Client => calculate_bucket_id(aid.to_string(), self.bucket_count),
+            TpcbMode::Server => self.branch_bucket_id(aid / ACCOUNTS_PER_BRANCH),
+        }
+    }
+}
+
+/// Where the tpcb-like transaction is executed.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TpcbMode {
+    /// Every statement is a separate router request.
+    #[default]
+    Client,
+    /// The whole transaction is a single call of the `tpcb_like` stored
+    /// procedure. Tellers, accounts and history are sharded by branch, so
+    /// the data has to be initialized in the same mode.
+    Server,
+}
+
+/// How the benchmark spaces are stored.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    /// Cartridge cluster, every request goes through `vshard.router`.
+    #[default]
+    Vshard,
+    /// Standalone instance or a single replicaset, spaces are accessed
+    /// directly and have no `bucket_id` field.
+    Box,
+}
+
+impl Display for Topology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Topology::Vshard => write!(f, "vshard"),
+            Topology::Box => write!(f, "box"),
+        }
+    }
+}
+
+/// Storage engine of the benchmark spaces.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    #[default]
+    Memtx,
+    /// Supports tree indexes only.
+    Vinyl,
+}
+
+impl Engine {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Engine::Memtx => "memtx",
+            Engine::Vinyl => "vinyl",
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexType {
+    Hash,
+    Tree,
+}
+
+impl IndexType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexType::Hash => "hash",
+            IndexType::Tree => "tree",
+        }
     }
 }
 
+/// Format of the result of a run or an initialization.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable report.
+    #[default]
+    Text,
+    /// JSON document.
+    Json,
+    /// Header and values line with a fixed set of columns.
+    Csv,
+}
+
+/// Field type of aid, tid and bid columns, chosen by scale by default.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdType {
+    Unsigned,
+    Integer,
+    Number,
+}
+
+impl IdType {
+    /// Like pgbench switches aid columns to bigint, ids are `integer` from
+    /// scale 20,000 and `unsigned` below.
+    fn for_scale(scale: u64) -> Self {
+        match scale {
+            ..=19_999 => IdType::Unsigned,
+            _ => IdType::Integer,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdType::Unsigned => "unsigned",
+            IdType::Integer => "integer",
+            IdType::Number => "number",
+        }
+    }
+
+    /// Checks that ids up to `max_id` are stored exactly.
+    fn validate(&self, max_id: u64) -> Result<(), anyhow::Error> {
+        match self {
+            IdType::Number if max_id > MAX_EXACT_NUMBER => Err(anyhow!(
+                "ids up to {max_id} don't fit the number type exactly, \
+                use --id-type unsigned or integer"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Index options of the vinyl engine, instance defaults are used for
+/// missing ones.
+#[derive(Debug, Clone, Default)]
+pub struct VinylOptions {
+    pub page_size: Option<u64>,
+    pub range_size: Option<u64>,
+    pub run_count_per_level: Option<u64>,
+    pub bloom_fpr: Option<f64>,
+}
+
+impl VinylOptions {
+    fn is_empty(&self) -> bool {
+        self.page_size.is_none() && self.run_count_per_level.is_none() && self.bloom_fpr.is_none()
+    }
+
+    /// Scales the default page and range sizes by the fillfactor. Vinyl
+    /// pages and ranges are never updated in place, so smaller ones make an
+    /// update rewrite less data, like free space in heap pages. Memtx
+    /// ignores the fillfactor with a warning.
+    fn apply_fillfactor(&mut self, engine: Engine, fillfactor: u64) -> Result<(), anyhow::Error> {
+        if !(10..=100).contains(&fillfactor) {
+            return Err(anyhow!("fillfactor must be in 10..=100, got {fillfactor}"));
+        }
+        match engine {
+            _ if fillfactor == 100 => {}
+            Engine::Memtx => message!("fillfactor {fillfactor} is ignored by memtx"),
+            Engine::Vinyl => {
+                self.page_size = self.page_size.or(Some(VINYL_PAGE_SIZE * fillfactor / 100));
+                self.range_size = Some(VINYL_RANGE_SIZE * fillfactor / 100);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Options of the transaction log, see `status::log`.
 #[derive(Debug, Clone)]
-pub struct InitSteps(HashSet<InitStep>);
+pub struct LogOptions {
+    pub prefix: String,
+    pub sampling_rate: Option<f64>,
+    /// interval of aggregates in seconds
+    pub aggregate_interval: Option<u64>,
+}
+
+impl Display for TpcbMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TpcbMode::Client => write!(f, "client"),
+            TpcbMode::Server => write!(f, "server"),
+        }
+    }
+}
+
+/// Init steps in the order they are executed.
+#[derive(Debug, Clone)]
+pub struct InitSteps(Vec<InitStep>);
+
+impl InitSteps {
+    pub fn iter(&self) -> impl Iterator<Item = &InitStep> {
+        self.0.iter()
+    }
+
+    /// Whether the steps work with spaces created before.
+    pub fn need_spaces(&self) -> bool {
+        !self.0.contains(&InitStep::Create) && self.0.iter().any(InitStep::uses_spaces)
+    }
+}
 
 impl<'a> TryFrom<&'a str> for InitSteps {
     type Error = anyhow::Error;
 
     fn try_from(steps: &'a str) -> Result<Self, Self::Error> {
-        Ok(Self(
-            steps
-                .chars()
-                .into_iter()
-                .map(|step| InitStep::try_from(step))
-                .collect::<Result<HashSet<InitStep>, anyhow::Error>>()?,
-        ))
+        let steps = steps
+            .chars()
+            .map(InitStep::try_from)
+            .collect::<Result<Vec<InitStep>, anyhow::Error>>()?;
+
+        let create = steps.iter().position(|step| *step == InitStep::Create);
+        let primary = steps.iter().position(|step| *step == InitStep::Primary);
+        let drop = steps.iter().position(|step| *step == InitStep::Drop);
+        for (index, step) in steps.iter().enumerate() {
+            if steps[..index].contains(step) {
+                return Err(anyhow!("init step '{}' is given twice", step.symbol()));
+            }
+            if !step.uses_spaces() {
+                continue;
+            }
+            match (create, drop) {
+                (Some(create), _) if index < create => {
+                    return Err(anyhow!(
+                        "init step '{}' must follow 't', spaces don't exist before it",
+                        step.symbol()
+                    ));
+                }
+                (None, Some(drop)) if drop < index => {
+                    return Err(anyhow!(
+                        "init step '{}' requires 't' after 'd', spaces are dropped",
+                        step.symbol()
+                    ));
+                }
+                _ => {}
+            }
+            // Spaces created by this run have no primary index until 'p'.
+            if matches!(step, InitStep::GenerateData | InitStep::GenerateDataServer)
+                && create.is_some()
+                && primary.is_none_or(|primary| index < primary)
+            {
+                return Err(anyhow!(
+                    "init step '{}' requires primary indexes, add 'p' before it",
+                    step.symbol()
+                ));
+            }
+        }
+        if create.zip(drop).is_some_and(|(create, drop)| create < drop) {
+            return Err(anyhow!("init step 'd' must precede 't'"));
+        }
+
+        Ok(Self(steps))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct ConnectionConfig {
-    pub addr: &'static str,
+    pub addr: String,
     pub timeout: Option<Duration>,
-    pub user: &'static str,
-    pub password: Option<&'static str>,
+    pub user: String,
+    pub password: Option<String>,
     pub connections: usize,
 }
 
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            addr: "localhost:3031".into(),
+            timeout: Some(Duration::from_millis(500)),
+            user: "admin".into(),
+            password: Some("admin".into()),
+            connections: 3,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TestConfig {
     pub ttbench_branches: u64,
@@ -68,8 +364,8 @@ impl Default for TestConfig {
     fn default() -> Self {
         Self {
             ttbench_branches: 1,
-            ttbench_tellers: 10,
-            ttbench_accounts: 100_000,
+            ttbench_tellers: TELLERS_PER_BRANCH,
+            ttbench_accounts: ACCOUNTS_PER_BRANCH,
         }
     }
 }
@@ -82,12 +378,16 @@ impl TryFrom<u64> for TestConfig {
             return Err(anyhow!("scale can't be 0"));
         }
 
-        println!("scaling factor: {}", scale);
+        message!("scaling factor: {}", scale);
+
+        let accounts = ACCOUNTS_PER_BRANCH
+            .checked_mul(scale)
+            .ok_or_else(|| anyhow!("scale {scale} is too large, account ids overflow"))?;
 
         Ok(TestConfig {
-            ttbench_branches: 1 * scale,
-            ttbench_tellers: 10 * scale,
-            ttbench_accounts: 100_000 * scale,
+            ttbench_branches: scale,
+            ttbench_tellers: TELLERS_PER_BRANCH * scale,
+            ttbench_accounts: accounts,
         })
     }
 }
@@ -109,55 +409,228 @@ impl Display for BenchMod {
 
 impl Config {
     pub fn new(cli: &Cli) -> Result<Self, anyhow::Error> {
-        let connections = 3;
-        let iterations_count = 1000;
+        let file = cli
+            .config
+            .as_deref()
+            .map(ConfigFile::read)
+            .transpose()?
+            .unwrap_or_default();
+        let mut instances = if file.instances.is_empty() {
+            vec![ConnectionConfig::default()]
+        } else {
+            file.instances
+                .into_iter()
+                .map(ConnectionConfig::from)
+                .collect()
+        };
+        if let Some(connections) = cli.connections {
+            for instance in instances.iter_mut() {
+                instance.connections = connections as usize;
+            }
+        }
         let mode = if cli.transactions.is_some() {
             BenchMod::Iterations
         } else {
             BenchMod::Time
         };
-        println!("transaction type: {}", "<builtin: TCP-B>"); // TODO: отображать режим транзакций
-        println!("number of jobs: {}", cli.jobs);
-        println!("number of connections: {}", cli.connections);
-        println!(
+        let test_config = TestConfig::try_from(cli.scale.or(file.scale).unwrap_or(DEFAULT_SCALE))?;
+        let bucket_count = file.bucket_count.unwrap_or(DEFAULT_BUCKET_COUNT);
+        let mut workloads = Vec::new();
+        for spec in &cli.builtins {
+            let (name, weight) = parse_weight(spec)?;
+            workloads.push(Workload {
+                kind: WorkloadKind::Builtin(name.parse()?),
+                weight,
+            });
+        }
+        for spec in &cli.files {
+            let (path, weight) = parse_weight(spec)?;
+            workloads.push(Workload {
+                kind: WorkloadKind::Script(Arc::new(Script::load(
+                    Path::new(path),
+                    &test_config,
+                    bucket_count,
+                )?)),
+                weight,
+            });
+        }
+        if workloads.is_empty() {
+            workloads.push(Workload {
+                kind: WorkloadKind::Builtin(Builtin::TpcbLike),
+                weight: 1,
+            });
+        }
+        if workloads.iter().all(|workload| workload.weight == 0) {
+            return Err(anyhow!("total weight of scripts must be positive"));
+        }
+        let primary_index = match (cli.engine, cli.primary_index) {
+            (Engine::Vinyl, Some(IndexType::Hash)) => {
+                return Err(anyhow!("vinyl supports tree indexes only"))
+            }
+            (Engine::Memtx, None) => IndexType::Hash,
+            (Engine::Vinyl, None) => IndexType::Tree,
+            (_, Some(index)) => index,
+        };
+        let mut vinyl = VinylOptions {
+            page_size: cli.vinyl_page_size,
+            range_size: None,
+            run_count_per_level: cli.vinyl_run_count_per_level,
+            bloom_fpr: cli.vinyl_bloom_fpr,
+        };
+        if cli.engine != Engine::Vinyl && !vinyl.is_empty() {
+            return Err(anyhow!("vinyl options require --engine vinyl"));
+        }
+        if cli.delta_min > cli.delta_max {
+            return Err(anyhow!(
+                "delta min {} is greater than delta max {}",
+                cli.delta_min,
+                cli.delta_max
+            ));
+        }
+        let max_tries = cli.max_tries.or(file.max_tries).unwrap_or(1);
+        if max_tries == 0 {
+            return Err(anyhow!("max tries must be positive"));
+        }
+        if let Some(rate) = cli.sampling_rate {
+            if !(rate > 0.0 && rate <= 1.0) {
+                return Err(anyhow!("sampling rate must be in (0, 1], got {rate}"));
+            }
+        }
+        let log = cli.log.then(|| LogOptions {
+            prefix: cli
+                .log_prefix
+                .clone()
+                .unwrap_or_else(|| "ttbench_log".into()),
+            sampling_rate: cli.sampling_rate,
+            aggregate_interval: cli.aggregate_interval,
+        });
+        let id_type = cli
+            .id_type
+            .unwrap_or_else(|| IdType::for_scale(test_config.ttbench_branches));
+        // Account ids are the largest ones.
+        id_type.validate(test_config.ttbench_accounts - 1)?;
+        let fillfactor = cli
+            .fillfactor
+            .or(file.fillfactor)
+            .unwrap_or(DEFAULT_FILLFACTOR);
+        vinyl.apply_fillfactor(cli.engine, fillfactor)?;
+        print_workloads(&workloads);
+        message!("topology: {}", cli.topology);
+        message!(
+            "storage engine: {}, primary index: {}",
+            cli.engine.name(),
+            primary_index.name()
+        );
+        message!("id type: {}", id_type.name());
+        message!("fillfactor: {fillfactor}");
+        message!("tpcb mode: {}", cli.tpcb_mode);
+        message!("delta range: {}..={}", cli.delta_min, cli.delta_max);
+        message!("number of jobs: {}", cli.jobs);
+        message!("maximum number of tries: {max_tries}");
+        let partial = workloads.iter().any(|workload| {
+            matches!(
+                workload.kind,
+                WorkloadKind::Builtin(Builtin::SimpleUpdate)
+                    | WorkloadKind::Builtin(Builtin::TpcbLike)
+            )
+        });
+        if max_tries > 1 && cli.topology == Topology::Vshard && partial {
+            message!(
+                "client-side transactions through vshard.router are committed statement by \
+                statement and are not retried"
+            );
+        }
+        message!(
+            "number of connections: {}",
+            instances
+                .iter()
+                .map(|instance| instance.connections)
+                .sum::<usize>()
+        );
+        message!(
             "number transactions per connection: {}",
             cli.transactions
                 .map(|num| num.to_string())
                 .unwrap_or("∞".to_string())
         );
-        println!("benchmark mode: {mode}");
+        message!("benchmark mode: {mode}");
+        if let BenchMod::Time = mode {
+            message!("duration: {} s", cli.time);
+        }
+        if let Some(rate) = cli.rate {
+            message!("rate limit: {rate} tps");
+        }
+        if let Some(limit) = cli.latency_limit {
+            message!("latency limit: {limit} ms");
+        }
         Ok(Config {
             mode,
-            test_config: TestConfig::try_from(cli.scale)?,
-            init_steps: InitSteps::try_from(
-                cli.init_steps
-                    .as_ref()
-                    .map(|steps| steps.as_str())
-                    .unwrap_or(DEFAULT_STEPS),
-            )?,
-            fillfactor: 100, // TODO
+            test_config,
+            workloads,
+            init_steps: InitSteps::try_from(cli.init_steps.as_deref().unwrap_or(DEFAULT_STEPS))?,
+            fillfactor,
             jobs: cli.jobs,
             transactions: cli.transactions.unwrap_or(0),
             time: Duration::from_secs(cli.time),
-            vebose_errors: false, // TODO
-            // TODO
-            instances: {
-                vec![ConnectionConfig {
-                    addr: "localhost:3031",
-                    timeout: Some(Duration::from_millis(500)),
-                    user: "admin",
-                    password: Some("admin"),
-                    connections,
-                }]
-            },
-            transactions_total: { iterations_count },
-            max_retries: u64::MAX, // TODO
-            bucket_count: 30000,   // TODO
+            progress: cli.progress.map(Duration::from_secs),
+            rate: cli.rate.map(|rate| rate as f64),
+            latency_limit: cli.latency_limit.map(Duration::from_millis),
+            verbose_errors: cli.verbose_errors,
+            failures_detailed: cli.failures_detailed,
+            report_per_statement: cli.report_per_statement,
+            log,
+            instances,
+            transactions_total: cli.transactions.unwrap_or(0) * cli.jobs,
+            max_tries,
+            bucket_count,
             keep_history: cli.keep_history,
+            tpcb_mode: cli.tpcb_mode,
+            topology: cli.topology,
+            init_batch_size: cli.init_batch_size,
+            vacuum_timeout: Duration::from_secs(cli.vacuum_timeout),
+            engine: cli.engine,
+            primary_index,
+            vinyl,
+            id_type,
+            delta_min: cli.delta_min,
+            delta_max: cli.delta_max,
+            check: cli.check,
+            output: cli.output,
         })
     }
 }
 
+/// Splits `name@weight` into name and weight, which is 1 by default. An `@`
+/// followed by a path separator is a part of the file name.
+fn parse_weight(spec: &str) -> Result<(&str, u64), anyhow::Error> {
+    match spec.rsplit_once('@') {
+        Some((name, weight)) if !weight.contains(std::path::is_separator) => Ok((
+            name,
+            weight
+                .parse()
+                .map_err(|err| anyhow!("invalid weight in '{spec}': {err}"))?,
+        )),
+        _ => Ok((spec, 1)),
+    }
+}
+
+fn print_workloads(workloads: &[Workload]) {
+    if let [workload] = workloads {
+        message!("transaction type: {workload}");
+        return;
+    }
+    message!("transaction type: multiple scripts");
+    let total: u64 = workloads.iter().map(|workload| workload.weight).sum();
+    for (index, workload) in workloads.iter().enumerate() {
+        message!(
+            "script {}: {workload}, weight: {} (targets {:.1}% of total)",
+            index + 1,
+            workload.weight,
+            workload.weight as f64 * 100.0 / total as f64
+        );
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum InitStep {
     Drop,
@@ -165,9 +638,43 @@ pub enum InitStep {
     Primary,
     Foreign,
     GenerateData,
+    /// Tuples are generated by the instances themselves.
+    GenerateDataServer,
     Vacuum,
 }
 
+impl InitStep {
+    fn symbol(&self) -> char {
+        match self {
+            InitStep::Drop => 'd',
+            InitStep::Create => 't',
+            InitStep::Primary => 'p',
+            InitStep::Foreign => 'f',
+            InitStep::GenerateData => 'g',
+            InitStep::GenerateDataServer => 'G',
+            InitStep::Vacuum => 'v',
+        }
+    }
+
+    fn uses_spaces(&self) -> bool {
+        !matches!(self, InitStep::Drop | InitStep::Create)
+    }
+}
+
+impl Display for InitStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitStep::Drop => write!(f, "drop spaces"),
+            InitStep::Create => write!(f, "create spaces"),
+            InitStep::Primary => write!(f, "primary indexes"),
+            InitStep::Foreign => write!(f, "secondary indexes"),
+            InitStep::GenerateData => write!(f, "client-side generate"),
+            InitStep::GenerateDataServer => write!(f, "server-side generate"),
+            InitStep::Vacuum => write!(f, "vacuum"),
+        }
+    }
+}
+
 impl TryFrom<char> for InitStep {
     type Error = anyhow::Error;
 
@@ -178,8 +685,164 @@ impl TryFrom<char> for InitStep {
             'p' => Ok(Self::Primary),
             'f' => Ok(Self::Foreign),
             'v' => Ok(Self::Vacuum),
-            'G' | 'g' => Ok(Self::GenerateData),
+            'g' => Ok(Self::GenerateData),
+            'G' => Ok(Self::GenerateDataServer),
             symbol => Err(anyhow!("unknown init step '{symbol}'")),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        args,
+        job::workload::{Builtin, WorkloadKind},
+    };
+
+    use super::{
+        parse_weight, Config, Engine, IdType, InitStep, InitSteps, TestConfig, VinylOptions,
+        MAX_EXACT_NUMBER, VINYL_RANGE_SIZE,
+    };
+
+    #[test]
+    fn test_init_steps() {
+        let steps = InitSteps::try_from("dtpgfv").unwrap();
+        assert_eq!(
+            steps.iter().collect::<Vec<_>>(),
+            [
+                &InitStep::Drop,
+                &InitStep::Create,
+                &InitStep::Primary,
+                &InitStep::GenerateData,
+                &InitStep::Foreign,
+                &InitStep::Vacuum,
+            ]
+        );
+
+        assert!(InitSteps::try_from("dtx").is_err());
+        assert!(!steps.need_spaces());
+        assert!(InitSteps::try_from("gv").unwrap().need_spaces());
+        assert!(!InitSteps::try_from("d").unwrap().need_spaces());
+    }
+
+    #[test]
+    fn test_invalid_init_steps() {
+        for steps in ["dtpgg", "gtp", "tgp", "tg", "dg", "tdpg", "ptg"] {
+            assert!(InitSteps::try_from(steps).is_err(), "{steps}");
+        }
+        for steps in ["dtpGfv", "tp", "f", "pg", "v", "dt"] {
+            assert!(InitSteps::try_from(steps).is_ok(), "{steps}");
+        }
+    }
+
+    #[test]
+    fn test_id_type() {
+        assert!(IdType::Number.validate(MAX_EXACT_NUMBER).is_ok());
+        assert!(IdType::Number.validate(MAX_EXACT_NUMBER + 1).is_err());
+        assert!(IdType::Number.validate(0).is_ok());
+        assert!(IdType::Number.validate(u64::MAX).is_err());
+        for id_type in [IdType::Unsigned, IdType::Integer] {
+            for max_id in [0, MAX_EXACT_NUMBER, MAX_EXACT_NUMBER + 1, u64::MAX] {
+                assert!(id_type.validate(max_id).is_ok(), "{id_type:?} {max_id}");
+            }
+        }
+        assert_eq!(IdType::for_scale(1), IdType::Unsigned);
+        assert_eq!(IdType::for_scale(19_999), IdType::Unsigned);
+        assert_eq!(IdType::for_scale(20_000), IdType::Integer);
+        assert_eq!(IdType::for_scale(u64::MAX), IdType::Integer);
+
+        assert_eq!(
+            TestConfig::try_from(20_000).unwrap().ttbench_accounts,
+            2_000_000_000
+        );
+        assert!(TestConfig::try_from(u64::MAX / 10).is_err());
+    }
+
+    #[test]
+    fn test_fillfactor() {
+        let mut vinyl = VinylOptions::default();
+        vinyl.apply_fillfactor(Engine::Vinyl, 100).unwrap();
+        assert_eq!((vinyl.page_size, vinyl.range_size), (None, None));
+
+        vinyl.apply_fillfactor(Engine::Vinyl, 50).unwrap();
+        assert_eq!(vinyl.page_size, Some(4096));
+        assert_eq!(vinyl.range_size, Some(VINYL_RANGE_SIZE / 2));
+
+        let mut vinyl = VinylOptions {
+            page_size: Some(16384),
+            ..Default::default()
+        };
+        vinyl.apply_fillfactor(Engine::Vinyl, 10).unwrap();
+        assert_eq!(vinyl.page_size, Some(16384));
+        assert_eq!(vinyl.range_size, Some(VINYL_RANGE_SIZE / 10));
+
+        let mut memtx = VinylOptions::default();
+        memtx.apply_fillfactor(Engine::Memtx, 90).unwrap();
+        assert_eq!((memtx.page_size, memtx.range_size), (None, None));
+        assert!(memtx.apply_fillfactor(Engine::Memtx, 5).is_err());
+    }
+
+    #[test]
+    fn test_parse_weight() {
+        assert_eq!(parse_weight("tpcb-like").unwrap(), ("tpcb-like", 1));
+        assert_eq!(parse_weight("select-only@5").unwrap(), ("select-only", 5));
+        assert_eq!(parse_weight("select-only@0").unwrap(), ("select-only", 0));
+        assert_eq!(
+            parse_weight("runs/a@b/script.toml@3").unwrap(),
+            ("runs/a@b/script.toml", 3)
+        );
+        assert_eq!(
+            parse_weight("runs/a@b/script.toml").unwrap(),
+            ("runs/a@b/script.toml", 1)
+        );
+        assert!(parse_weight("tpcb-like@").is_err());
+        assert!(parse_weight("tpcb-like@-1").is_err());
+        assert!(parse_weight("tpcb-like@heavy").is_err());
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!("tpcb-like".parse::<Builtin>().unwrap(), Builtin::TpcbLike);
+        assert_eq!(
+            "simple-update".parse::<Builtin>().unwrap(),
+            Builtin::SimpleUpdate
+        );
+        assert_eq!(
+            "select-only".parse::<Builtin>().unwrap(),
+            Builtin::SelectOnly
+        );
+        let err = "tpcb".parse::<Builtin>().unwrap_err();
+        assert!(err.to_string().contains("unknown builtin script 'tpcb'"));
+
+        let config = Config::new(&args::init([
+            "ttbench",
+            "run",
+            "-b",
+            "select-only@3",
+            "-b",
+            "simple-update@0",
+        ]))
+        .unwrap();
+        let workloads = config
+            .workloads
+            .iter()
+            .map(|workload| match workload.kind {
+                WorkloadKind::Builtin(builtin) => (builtin, workload.weight),
+                WorkloadKind::Script(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            workloads,
+            [(Builtin::SelectOnly, 3), (Builtin::SimpleUpdate, 0)]
+        );
+
+        for builtins in [
+            ["-b", "tpcb-like@0"],
+            ["-b", "unknown@2"],
+            ["-b", "tpcb-like@"],
+        ] {
+            let cli = args::init(["ttbench", "run"].into_iter().chain(builtins));
+            assert!(Config::new(&cli).is_err(), "{builtins:?}");
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use super::ConnectionConfig;
+
+/// Contents of the file passed with `--config`.
+///
+/// ```toml
+/// bucket_count = 30000
+/// max_tries = 10
+/// fillfactor = 100
+/// scale = 10
+///
+/// [[instances]]
+/// addr = "localhost:3031"
+/// user = "admin"
+/// password = "admin"
+/// timeout = 500 # milliseconds
+/// connections = 3
+/// ```
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub instances: Vec<InstanceConfig>,
+    pub bucket_count: Option<u32>,
+    pub max_tries: Option<u64>,
+    pub fillfactor: Option<u64>,
+    pub scale: Option<u64>,
+}
+
+impl ConfigFile {
+    pub fn read(path: &Path) -> Result<Self, anyhow::Error> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("failed to read config {}: {err}", path.display()))?;
+        content
+            .parse()
+            .map_err(|err| anyhow!("failed to parse config {}: {err}", path.display()))
+    }
+}
+
+impl std::str::FromStr for ConfigFile {
+    type Err = toml::de::Error;
+
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        toml::from_str(content)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct InstanceConfig {
+    pub addr: String,
+    pub user: String,
+    pub password: Option<String>,
+    /// Request timeout in milliseconds, 500 by default.
+    pub timeout: Option<u64>,
+    pub connections: Option<usize>,
+}
+
+impl From<InstanceConfig> for ConnectionConfig {
+    fn from(instance: InstanceConfig) -> Self {
+        let default = ConnectionConfig::default();
+        ConnectionConfig {
+            addr: instance.addr,
+            timeout: instance
+                .timeout
+                .map(Duration::from_millis)
+                .or(default.timeout),
+            user: instance.user,
+            password: instance.password,
+            connections: instance.connections.unwrap_or(default.connections),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::config::ConnectionConfig;
+
+    use super::{ConfigFile, InstanceConfig};
+
+    #[test]
+    fn test_parse() {
+        let file: ConfigFile = r#"
+bucket_count = 3000
+scale = 10
+
+[[instances]]
+addr = "localhost:3031"
+user = "admin"
+password = "admin"
+timeout = 200
+
+[[instances]]
+addr = "localhost:3032"
+user = "guest"
+connections = 1
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            file,
+            ConfigFile {
+                instances: vec![
+                    InstanceConfig {
+                        addr: "localhost:3031".into(),
+                        user: "admin".into(),
+                        password: Some("admin".into()),
+                        timeout: Some(200),
+                        connections: None,
+                    },
+                    InstanceConfig {
+                        addr: "localhost:3032".into(),
+                        user: "guest".into(),
+                        password: None,
+                        timeout: None,
+                        connections: Some(1),
+                    },
+                ],
+                bucket_count: Some(3000),
+                max_tries: None,
+                fillfactor: None,
+                scale: Some(10),
+            }
+        );
+
+        let instances: Vec<_> = file
+            .instances
+            .into_iter()
+            .map(ConnectionConfig::from)
+            .collect();
+        assert_eq!(instances[0].timeout, Some(Duration::from_millis(200)));
+        assert_eq!(instances[0].connections, 3);
+        assert_eq!(instances[1].timeout, Some(Duration::from_millis(500)));
+        assert_eq!(instances[1].connections, 1);
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let err = "scael = 10".parse::<ConfigFile>().unwrap_err();
+        assert!(err.to_string().contains("unknown field `scael`"));
+
+        let err = r#"
+[[instances]]
+addr = "localhost:3031"
+user = "admin"
+pasword = "admin"
+"#
+        .parse::<ConfigFile>()
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown field `pasword`"));
+    }
+}
@@ -1,96 +1,191 @@
 use anyhow::anyhow;
 use std::{
+    fmt::Debug,
     future::Future,
-    ops::AddAssign,
     pin::Pin,
     sync::Arc,
-    task::{Poll, Waker},
+    task::{ready, Poll},
+    time::{Duration, Instant},
 };
 
+use tokio::time::Sleep;
+
 use crate::{
     config::Config,
-    status::{Status, Target},
+    status::{
+        log::{Outcome, TransactionLog},
+        statements::Statements,
+        Status, Target,
+    },
 };
 
-use self::connections::Pool;
+use self::{
+    connections::Pool,
+    error::{error_code, ErrorClass},
+    schema::SpaceIds,
+};
 
 // TODO remove pub
-pub mod accounts;
-pub mod branches;
+pub mod check;
 pub mod connections;
 pub mod create;
 pub mod drop;
-pub mod tellers;
+pub mod error;
+pub mod generate;
+pub mod index;
+pub mod schema;
+pub mod script;
+pub mod select_only;
+pub mod simple_update;
 pub mod tpcb;
+pub mod vacuum;
+pub mod workload;
 
 pub struct JobConfig {
     pub config: Config,
     pub connections: Pool,
+    pub spaces: SpaceIds,
+    pub statements: Statements,
 }
 
 pub struct Job<T, S, F, O>
 where
     T: Target + Send + Sync,
+    T::Output: TaskInfo + Clone + Unpin + Debug,
     S: Spawn<T::Output, Output = F> + Unpin + Clone,
     F: Future<Output = Result<O, anyhow::Error>> + Send,
 {
     id: u64,
-    task: Pin<Box<F>>,
+    task: Option<Pin<Box<F>>>,
+    task_started: Instant,
+    /// wait for the scheduled start of the next task when the load is
+    /// rate limited
+    delay: Option<(Pin<Box<Sleep>>, Instant)>,
+    /// values of the running task, to retry it with the same ones
+    value: Option<T::Output>,
+    tries: u64,
+    /// lag of the running task behind its schedule with `--rate`
+    lag: Option<Duration>,
+    log: Option<TransactionLog>,
     spawner: S,
     job_config: Pin<Arc<JobConfig>>,
-    job_retries: u64,
     status: Pin<Arc<Status<T>>>,
 }
 
 impl<T, S, F, O> Future for Job<T, S, F, O>
 where
     T: Target + Send + Sync,
+    T::Output: TaskInfo + Clone + Unpin + Debug,
     S: Spawn<T::Output, Output = F> + Unpin + Clone,
     F: Future<Output = Result<O, anyhow::Error>> + Send,
 {
-    type Output = Result<O, anyhow::Error>;
+    type Output = Result<(), anyhow::Error>;
 
     fn poll(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Self::Output> {
-        match (
-            Pin::new(&mut self.task).poll(cx),
-            self.status.target.is_reached()?,
-        ) {
-            (Poll::Ready(out), true) if out.is_ok() => {
-                if let Err(err) = &out {
-                    self.status.logger.error(err);
-                    if self.job_retries > self.job_config.config.max_retries {
-                        return Poll::Ready(out);
+        loop {
+            if let Some(task) = self.task.as_mut() {
+                let out = ready!(task.as_mut().poll(cx));
+                self.task = None;
+                let outcome = match out {
+                    Ok(_) => {
+                        let elapsed = self.task_started.elapsed();
+                        self.status.progress.update(elapsed);
+                        if self.value.as_ref().is_some_and(TaskInfo::writes_history) {
+                            self.status.progress.history_written();
+                        }
+                        Outcome::Done(elapsed)
                     }
-                    self.job_retries.add_assign(1);
+                    Err(err) => {
+                        let class = ErrorClass::classify(&err);
+                        let config = &self.job_config.config;
+                        let atomic = self
+                            .value
+                            .as_ref()
+                            .is_some_and(|value| value.is_atomic(config));
+                        if class.is_retryable() && atomic && self.tries < config.max_tries {
+                            self.status.progress.retry(class, self.tries == 1);
+                            self.spawn();
+                            continue;
+                        }
+                        let code = error_code(&err);
+                        self.status.progress.fail(class, code.clone());
+                        self.status.logger.error(self.id, self.value.as_ref(), &err);
+                        Outcome::Failed(code)
+                    }
+                };
+                let script = self.value.as_ref().map_or(0, TaskInfo::script_id);
+                if let Err(err) = self.log(script, outcome) {
+                    return Poll::Ready(Err(err));
                 }
-                Poll::Ready(out)
+                self.value = None;
+                self.schedule();
             }
-            (Poll::Ready(out), false) => {
-                if let Err(err) = &out {
-                    self.status.logger.error(err);
+
+            let scheduled = match self.delay.as_mut() {
+                Some((delay, scheduled)) => {
+                    let scheduled = *scheduled;
+                    ready!(delay.as_mut().poll(cx));
+                    self.delay = None;
+                    Some(scheduled)
                 }
-                if let Some(value) = self.status.make_progress()? {
-                    self.task =
-                        Box::pin(self.spawner.clone().spawn(self.job_config.clone(), value));
+                None => None,
+            };
 
-                    Waker::wake_by_ref(cx.waker());
+            // The task is spawned only while the target is not reached, so
+            // in-flight transactions are allowed to finish when it is.
+            let value = match self.status.make_progress() {
+                Ok(Some(value)) => value,
+                Ok(None) => {
+                    let finished = match self.log.as_mut() {
+                        Some(log) => log.finish(),
+                        None => Ok(()),
+                    };
+                    return Poll::Ready(finished);
                 }
-                Poll::Pending
+                Err(err) => return Poll::Ready(Err(err)),
+            };
+            self.lag = scheduled.map(|scheduled| scheduled.elapsed());
+            if let Some(scheduled) = scheduled {
+                if self.status.is_late(scheduled) {
+                    self.status.progress.skip();
+                    if let Err(err) = self.log(value.script_id(), Outcome::Skipped) {
+                        return Poll::Ready(Err(err));
+                    }
+                    self.schedule();
+                    continue;
+                }
+                self.status.progress.lag(scheduled.elapsed());
             }
-            _ => {
-                Waker::wake_by_ref(cx.waker());
-                Poll::Pending
+            if value.writes_history() {
+                self.status.progress.history_started();
             }
+            self.value = Some(value);
+            self.tries = 0;
+            self.task_started = Instant::now();
+            self.spawn();
         }
     }
 }
 
+impl<T, S, F, O> Drop for Job<T, S, F, O>
+where
+    T: Target + Send + Sync,
+    T::Output: TaskInfo + Clone + Unpin + Debug,
+    S: Spawn<T::Output, Output = F> + Unpin + Clone,
+    F: Future<Output = Result<O, anyhow::Error>> + Send,
+{
+    fn drop(&mut self) {
+        self.status.progress.job_finished();
+    }
+}
+
 impl<T, S, F, O> Job<T, S, F, O>
 where
     T: Target + Send + Sync,
+    T::Output: TaskInfo + Clone + Unpin + Debug,
     S: Spawn<T::Output, Output = F> + Unpin + Clone,
     F: Future<Output = Result<O, anyhow::Error>> + Send,
 {
@@ -100,19 +195,82 @@ where
         job_config: Pin<Arc<JobConfig>>,
         status: Pin<Arc<Status<T>>>,
     ) -> Result<Self, anyhow::Error> {
-        if let Some(value) = status.make_progress()? {
-            return Ok(Job {
-                id,
-                task: Box::pin(spawner.clone().spawn(job_config.clone(), value)),
-                spawner,
-                job_config,
-                job_retries: 0,
-                status,
-            });
+        if status.is_reached()? {
+            return Err(anyhow!(
+                "failed to build job, because test target already reached"
+            ));
+        }
+        let log = match status.log.as_ref() {
+            Some(options) => Some(TransactionLog::create(options, id)?),
+            None => None,
+        };
+        status.progress.job_started();
+        let mut job = Job {
+            id,
+            task: None,
+            task_started: Instant::now(),
+            delay: None,
+            value: None,
+            tries: 0,
+            lag: None,
+            log,
+            spawner,
+            job_config,
+            status,
+        };
+        job.schedule();
+        Ok(job)
+    }
+
+    /// Starts the task for the current values, latency of a retried one
+    /// includes all of its tries.
+    fn spawn(&mut self) {
+        let value = self.value.clone().expect("values of the task are set");
+        self.tries += 1;
+        self.task = Some(Box::pin(
+            self.spawner.clone().spawn(self.job_config.clone(), value),
+        ));
+    }
+
+    /// Writes the outcome of a task to the transaction log.
+    fn log(&mut self, script: usize, outcome: Outcome) -> Result<(), anyhow::Error> {
+        match self.log.as_mut() {
+            Some(log) => log.record(script, outcome, self.lag),
+            None => Ok(()),
         }
-        Err(anyhow!(
-            "failed to build job, because test target already reached"
-        ))
+    }
+
+    fn schedule(&mut self) {
+        self.delay = self
+            .status
+            .schedule()
+            .map(|at| (Box::pin(tokio::time::sleep_until(at.into())), at));
+    }
+}
+
+/// Properties of the values of a task which a job needs.
+pub trait TaskInfo {
+    /// Index of the script the task runs, written to the transaction log.
+    fn script_id(&self) -> usize;
+
+    /// Whether a failed task leaves no changes behind, so it may be retried.
+    fn is_atomic(&self, config: &Config) -> bool;
+
+    /// Whether the task inserts a history row, counted for `--check`.
+    fn writes_history(&self) -> bool;
+}
+
+impl TaskInfo for u64 {
+    fn script_id(&self) -> usize {
+        0
+    }
+
+    fn is_atomic(&self, _config: &Config) -> bool {
+        true
+    }
+
+    fn writes_history(&self) -> bool {
+        false
     }
 }
 
@@ -0,0 +1,478 @@
+use std::{pin::Pin, sync::Arc};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use tarantool_rs::ExecutorExt;
+
+use crate::{
+    config::{Config, Topology},
+    job::JobConfig,
+    status::Progress,
+    utils::message,
+};
+
+use super::{
+    schema::{ACCOUNTS, BRANCHES, HISTORY, TELLERS},
+    workload::{Builtin, WorkloadKind},
+};
+
+/// Returns the number of tuples in each space, -1 for missing ones.
+const COUNT: &str = r#"
+local names = ...
+local counts = {}
+for i, name in ipairs(names) do
+    local space = box.space[name]
+    counts[i] = space ~= nil and space:count() or -1
+end
+return counts
+"#;
+
+/// Returns the sum of the given field over each space.
+const SUM: &str = r#"
+local spaces = ...
+local sums = {}
+for i, space in ipairs(spaces) do
+    local name, field = unpack(space)
+    if box.space[name] == nil then
+        error(string.format("space '%s' not found", name))
+    end
+    local sum = 0
+    for _, tuple in box.space[name]:pairs() do
+        sum = sum + tuple[field]
+    end
+    sums[i] = sum
+end
+return sums
+"#;
+
+/// Balances of every table and deltas of the history.
+const SUMS: [(&str, u32); 4] = [(ACCOUNTS, 2), (TELLERS, 2), (BRANCHES, 2), (HISTORY, 5)];
+
+pub async fn run(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    let names = [ACCOUNTS, TELLERS, BRANCHES, HISTORY];
+    let counts = counts(&job_config, &names).await?;
+
+    let test_config = &job_config.config.test_config;
+    let expected = [
+        Some(test_config.ttbench_accounts),
+        Some(test_config.ttbench_tellers),
+        Some(test_config.ttbench_branches),
+        None,
+    ];
+    let mut problems = Vec::new();
+    for ((name, count), expected) in names.iter().zip(counts).zip(expected) {
+        match (u64::try_from(count), expected) {
+            (Err(_), _) => problems.push(format!("space '{name}' not found")),
+            (Ok(count), Some(expected)) if count != expected => problems.push(format!(
+                "space '{name}' has {count} tuples, {expected} expected"
+            )),
+            (Ok(count), _) => message!("{name}: {count} tuples"),
+        }
+    }
+    if problems.is_empty() {
+        problems = consistency(&job_config, None).await?.problems;
+    }
+    if !problems.is_empty() {
+        return Err(anyhow!("check failed: {}", problems.join(", ")));
+    }
+    message!("check passed");
+
+    Ok(())
+}
+
+/// Sums of balances and of history deltas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sums {
+    pub accounts: i64,
+    pub tellers: i64,
+    pub branches: i64,
+    pub deltas: i64,
+}
+
+/// State of the spaces before a run, to compare its changes with the
+/// transactions of the run.
+pub struct Snapshot {
+    pub history: u64,
+    pub sums: Sums,
+}
+
+/// Outcome of `consistency`, for the report.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// The balances can't be compared with the history, and nothing else
+    /// failed.
+    NotCheckable,
+}
+
+impl CheckStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CheckStatus::Passed => "passed",
+            CheckStatus::Failed => "failed",
+            CheckStatus::NotCheckable => "not_checkable",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct CheckSummary {
+    pub status: CheckStatus,
+    pub problems: Vec<String>,
+}
+
+impl CheckSummary {
+    fn new(problems: Vec<String>, checkable: bool) -> Self {
+        let status = match (problems.is_empty(), checkable) {
+            (false, _) => CheckStatus::Failed,
+            (true, false) => CheckStatus::NotCheckable,
+            (true, true) => CheckStatus::Passed,
+        };
+        Self { status, problems }
+    }
+}
+
+/// The run checked by `consistency`.
+pub struct RunHistory<'a> {
+    pub before: Snapshot,
+    pub progress: &'a Progress,
+}
+
+pub async fn snapshot(job_config: &JobConfig) -> Result<Snapshot, anyhow::Error> {
+    Ok(Snapshot {
+        history: history_count(job_config).await?,
+        sums: sums(job_config).await?,
+    })
+}
+
+/// Checks that every balance change is recorded in the history. Without a
+/// run the sums over the whole spaces are compared; with one the changes
+/// made by the run are compared, according to its workloads.
+pub async fn consistency(
+    job_config: &JobConfig,
+    run: Option<RunHistory<'_>>,
+) -> Result<CheckSummary, anyhow::Error> {
+    let after = sums(job_config).await?;
+    message!(
+        "sum of balances: accounts {}, tellers {}, branches {}, history deltas {}",
+        after.accounts,
+        after.tellers,
+        after.branches,
+        after.deltas
+    );
+
+    let Some(run) = run else {
+        message!(
+            "teller balances are compared with history deltas by run --check only, \
+            as simple updates change accounts only"
+        );
+        return Ok(CheckSummary::new(compare(&after, None), true));
+    };
+
+    let config = &job_config.config;
+    let builtins = builtins(config);
+    let (mut problems, checkable) = match &builtins {
+        Some(builtins) if builtins.iter().all(|builtin| builtin.is_atomic(config)) => {
+            let before = run.before.sums;
+            let change = Sums {
+                accounts: after.accounts.wrapping_sub(before.accounts),
+                tellers: after.tellers.wrapping_sub(before.tellers),
+                branches: after.branches.wrapping_sub(before.branches),
+                deltas: after.deltas.wrapping_sub(before.deltas),
+            };
+            let problems = compare(&change, Some(builtins))
+                .into_iter()
+                .map(|problem| format!("changed by the run: {problem}"))
+                .collect();
+            (problems, true)
+        }
+        Some(_) => {
+            message!(
+                "balances are not checkable: a failed client-side transaction through \
+                vshard.router may leave a balance change without its history row"
+            );
+            (Vec::new(), false)
+        }
+        None => {
+            message!("balances are not compared with custom scripts");
+            (Vec::new(), false)
+        }
+    };
+
+    let after = history_count(job_config).await?;
+    let added = after.saturating_sub(run.before.history);
+    let (started, written) = run.progress.history_transactions();
+    message!(
+        "history rows added: {added}, transactions writing history started: {started}, \
+        succeeded: {written}"
+    );
+    match builtins {
+        Some(_) => problems.extend(compare_history(added, started, written)),
+        None => message!("history rows are not compared with custom scripts"),
+    }
+
+    Ok(CheckSummary::new(problems, checkable))
+}
+
+/// Compares the history rows added by a run with its transactions which
+/// insert one. Failed ones may have been committed before the error reached
+/// the client.
+fn compare_history(added: u64, started: u64, written: u64) -> Option<String> {
+    (!(written..=started).contains(&added)).then(|| {
+        format!(
+            "{added} history rows added by {written} successful of {started} transactions \
+            writing history"
+        )
+    })
+}
+
+/// Compares balance sums, or their changes, with history deltas. Both
+/// builtins change accounts and write the history, and only tpcb-like
+/// changes tellers and branches, so tellers are compared with the deltas
+/// only when `builtins`, which made the changes, are known and have no
+/// simple updates.
+fn compare(sums: &Sums, builtins: Option<&[Builtin]>) -> Vec<String> {
+    let Sums {
+        accounts,
+        tellers,
+        branches,
+        deltas,
+    } = *sums;
+    let mut problems = Vec::new();
+    if accounts != deltas {
+        problems.push(format!(
+            "sum of account balances {accounts} differs from sum of history deltas {deltas}"
+        ));
+    }
+    if tellers != branches {
+        problems.push(format!(
+            "sum of teller balances {tellers} differs from sum of branch balances {branches}"
+        ));
+    }
+    if builtins.is_some_and(|builtins| !builtins.contains(&Builtin::SimpleUpdate))
+        && tellers != deltas
+    {
+        problems.push(format!(
+            "sum of teller balances {tellers} differs from sum of history deltas {deltas}"
+        ));
+    }
+    problems
+}
+
+async fn history_count(job_config: &JobConfig) -> Result<u64, anyhow::Error> {
+    counts(job_config, &[HISTORY])
+        .await?
+        .first()
+        .copied()
+        .and_then(|count| u64::try_from(count).ok())
+        .ok_or_else(|| anyhow!("space '{HISTORY}' not found"))
+}
+
+/// Builtin workloads of the run, `None` if there are custom scripts.
+fn builtins(config: &Config) -> Option<Vec<Builtin>> {
+    config
+        .workloads
+        .iter()
+        .map(|workload| match workload.kind {
+            WorkloadKind::Builtin(builtin) => Some(builtin),
+            WorkloadKind::Script(_) => None,
+        })
+        .collect()
+}
+
+/// Sums balances and history deltas over all storages.
+async fn sums(job_config: &JobConfig) -> Result<Sums, anyhow::Error> {
+    let conn = job_config.connections.get_maintenance_connection();
+    let sums = match job_config.config.topology {
+        Topology::Vshard => conn
+            .eval(
+                r#"
+local sum, spaces = ...
+local nb = require("net.box")
+local shards, err = vshard.router.routeall()
+if err then
+    return nil, err
+end
+local total = {}
+for i = 1, #spaces do
+    total[i] = 0
+end
+for _, shard in pairs(shards) do
+    local conn, err = nb.connect(shard.master.uri)
+    if err then
+        return nil, err
+    end
+
+    local ok, sums = pcall(conn.eval, conn, sum, {spaces})
+    conn:close()
+    if not ok then
+        return nil, tostring(sums)
+    end
+    for i, value in ipairs(sums) do
+        total[i] = total[i] + value
+    end
+end
+return total
+                "#,
+                (SUM, SUMS),
+            )
+            .await
+            .and_then(|response| response.decode_result::<Vec<i64>>()),
+        Topology::Box => conn
+            .eval(SUM, (SUMS,))
+            .await
+            .and_then(|response| response.decode_result::<Vec<i64>>()),
+    }?;
+    let [accounts, tellers, branches, deltas] = sums[..] else {
+        return Err(anyhow!("{} sums returned, 4 expected", sums.len()));
+    };
+    Ok(Sums {
+        accounts,
+        tellers,
+        branches,
+        deltas,
+    })
+}
+
+/// Returns the number of tuples in each space, -1 for missing ones.
+pub async fn counts(job_config: &JobConfig, names: &[&str]) -> Result<Vec<i64>, anyhow::Error> {
+    match job_config.config.topology {
+        Topology::Vshard => vshard(job_config, names).await,
+        Topology::Box => standalone(job_config, names).await,
+    }
+}
+
+/// Sums tuple counts over the masters of all replicasets.
+async fn vshard(job_config: &JobConfig, names: &[&str]) -> Result<Vec<i64>, anyhow::Error> {
+    job_config
+        .connections
+        .get_maintenance_connection()
+        .eval(
+            r#"
+local count, names = ...
+local nb = require("net.box")
+local shards, err = vshard.router.routeall()
+if err then
+    return nil, err
+end
+local total = {}
+for i = 1, #names do
+    total[i] = 0
+end
+for _, shard in pairs(shards) do
+    local uri = shard.master.uri
+    local conn, err = nb.connect(uri)
+    if err then
+        return nil, err
+    end
+
+    local ok, counts = pcall(conn.eval, conn, count, {names})
+    conn:close()
+    if not ok then
+        return nil, counts
+    end
+    for i, count in ipairs(counts) do
+        if count < 0 or total[i] < 0 then
+            total[i] = -1
+        else
+            total[i] = total[i] + count
+        end
+    end
+end
+return total
+            "#,
+            (COUNT, names),
+        )
+        .await
+        .and_then(|response| response.decode_result::<Vec<i64>>())
+        .map_err(Into::into)
+}
+
+async fn standalone(job_config: &JobConfig, names: &[&str]) -> Result<Vec<i64>, anyhow::Error> {
+    job_config
+        .connections
+        .get_maintenance_connection()
+        .eval(COUNT, (names,))
+        .await
+        .and_then(|response| response.decode_result::<Vec<i64>>())
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::job::workload::Builtin;
+
+    use super::{compare, compare_history, CheckStatus, CheckSummary, Sums};
+
+    #[test]
+    fn test_compare() {
+        let consistent = Sums {
+            accounts: 100,
+            tellers: 100,
+            branches: 100,
+            deltas: 100,
+        };
+        assert!(compare(&consistent, None).is_empty());
+        assert!(compare(&consistent, Some(&[Builtin::TpcbLike])).is_empty());
+
+        // Simple updates leave tellers and branches behind the history.
+        let simple_update = Sums {
+            tellers: 40,
+            branches: 40,
+            ..consistent
+        };
+        assert!(compare(&simple_update, None).is_empty());
+        assert!(compare(&simple_update, Some(&[Builtin::SimpleUpdate])).is_empty());
+        assert!(compare(
+            &simple_update,
+            Some(&[Builtin::TpcbLike, Builtin::SimpleUpdate])
+        )
+        .is_empty());
+        assert_eq!(
+            compare(&simple_update, Some(&[Builtin::TpcbLike])),
+            ["sum of teller balances 40 differs from sum of history deltas 100"]
+        );
+
+        let lost = Sums {
+            accounts: 90,
+            branches: 70,
+            ..consistent
+        };
+        assert_eq!(
+            compare(&lost, None),
+            [
+                "sum of account balances 90 differs from sum of history deltas 100",
+                "sum of teller balances 100 differs from sum of branch balances 70",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_history() {
+        assert_eq!(compare_history(5, 5, 5), None);
+        // Failed transactions may or may not have inserted their row.
+        assert_eq!(compare_history(3, 5, 3), None);
+        assert_eq!(compare_history(4, 5, 3), None);
+        assert_eq!(compare_history(5, 5, 3), None);
+        assert_eq!(
+            compare_history(6, 5, 3).unwrap(),
+            "6 history rows added by 3 successful of 5 transactions writing history"
+        );
+        assert!(compare_history(2, 5, 3).is_some());
+    }
+
+    #[test]
+    fn test_check_status() {
+        assert_eq!(CheckSummary::new(vec![], true).status, CheckStatus::Passed);
+        assert_eq!(
+            CheckSummary::new(vec![], false).status,
+            CheckStatus::NotCheckable
+        );
+        for checkable in [true, false] {
+            let check = CheckSummary::new(vec!["history rows differ".into()], checkable);
+            assert_eq!(check.status, CheckStatus::Failed);
+        }
+    }
+}
@@ -3,13 +3,17 @@ use std::{
     sync::Mutex,
 };
 
-use tarantool_rs::Connection;
+use anyhow::anyhow;
+use tarantool_rs::{Connection, ExecutorExt};
 
 use crate::config::ConnectionConfig;
 
 pub struct Pool {
     index: Mutex<usize>,
     pool: Vec<Connection>,
+    /// connection without request timeout for long running initialization
+    /// and maintenance requests
+    maintenance: Connection,
 }
 
 impl Pool {
@@ -32,19 +36,55 @@ impl Pool {
                 pool.push(
                     Connection::builder()
                         .timeout(*timeout)
-                        .auth(user, *password)
-                        .build(*addr)
+                        .auth(user, password.as_deref())
+                        .build(addr.clone())
                         .await?,
                 );
             }
         }
 
+        let ConnectionConfig {
+            addr,
+            user,
+            password,
+            ..
+        } = &instances[0];
+        let maintenance = Connection::builder()
+            .auth(user, password.as_deref())
+            .build(addr.clone())
+            .await?;
+
         Ok(Self {
             index: Mutex::new(0),
             pool,
+            maintenance,
         })
     }
 
+    /// Makes `vshard` global on routers so its functions may be called
+    /// over IPROTO.
+    pub async fn expose_vshard(&self) -> Result<(), anyhow::Error> {
+        for conn in self.pool.iter().chain([&self.maintenance]) {
+            let exposed = conn
+                .eval(
+                    r#"
+local service_registry = require("cartridge.service-registry")
+if service_registry.get("vshard-router") then
+    _G.vshard = require("vshard")
+end
+return rawget(_G, "vshard") ~= nil
+                    "#,
+                    (),
+                )
+                .await
+                .and_then(|response| response.decode_result::<bool>())?;
+            if !exposed {
+                return Err(anyhow!("vshard-router role is not enabled"));
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_connection(&self) -> &Connection {
         let mut locked = self.index.lock().unwrap();
         let mut index = locked.add(1);
@@ -56,4 +96,8 @@ impl Pool {
         }
         &self.pool[index]
     }
+
+    pub fn get_maintenance_connection(&self) -> &Connection {
+        &self.maintenance
+    }
 }
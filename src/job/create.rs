@@ -1,186 +1,108 @@
 use std::{pin::Pin, sync::Arc};
 
-use serde::Serialize;
-use tarantool_rs::{ExecutorExt, Tuple};
-
-use crate::config::InitStep;
-
-use super::JobConfig;
-
-#[derive(Serialize)]
-pub struct CreateSpaceOptions {
-    if_not_exists: bool,
-    format: Vec<FieldFormat>,
-}
-
-impl Tuple for CreateSpaceOptions {
-    fn encode_into_writer<W: std::io::Write>(
-        &self,
-        mut buf: W,
-    ) -> Result<(), tarantool_rs::errors::EncodingError> {
-        rmp_serde::encode::write_named(&mut buf, self)?;
-        Ok(())
-    }
+use tarantool_rs::ExecutorExt;
+
+use crate::config::Topology;
+
+use super::{
+    schema::{self, named},
+    JobConfig,
+};
+
+/// `tpcb_like` executes the tpcb-like transaction on the instance, see
+/// `tpcb::vshard_server`, the `bucket_id` is omitted for the box topology.
+/// `ttbench_replace_batch` inserts tuples generated by the client in one
+/// transaction.
+const PROCEDURES: &str = r#"
+rawset(_G, "tpcb_like", function(aid, tid, bid, delta, uuid, time, bucket_id)
+    return box.atomic(function()
+        local account = box.space.ttbench_accounts:update(aid, {{"+", "abalance", delta}})
+        if account == nil then
+            error(string.format("account %s is not stored on this replicaset", aid))
+        end
+        if box.space.ttbench_tellers:update(tid, {{"+", "tbalance", delta}}) == nil then
+            error(string.format("teller %s is not stored on this replicaset", tid))
+        end
+        if box.space.ttbench_branches:update(bid, {{"+", "bbalance", delta}}) == nil then
+            error(string.format("branch %s is not stored on this replicaset", bid))
+        end
+        box.space.ttbench_history:insert({uuid, tid, bid, aid, delta, time, bucket_id})
+        return account.abalance
+    end)
+end)
+rawset(_G, "ttbench_replace_batch", function(space, tuples)
+    return box.atomic(function()
+        for _, tuple in ipairs(tuples) do
+            box.space[space]:replace(tuple)
+        end
+        return #tuples
+    end)
+end)
+if not box.info.ro then
+    box.schema.func.create("tpcb_like", { if_not_exists = true })
+    box.schema.func.create("ttbench_replace_batch", { if_not_exists = true })
+end
+"#;
+
+pub async fn run(job_config: Pin<Arc<JobConfig>>, id: u64) -> Result<(), anyhow::Error> {
+    let result = match job_config.config.topology {
+        Topology::Vshard => vshard(job_config.clone(), id).await,
+        Topology::Box => standalone(job_config.clone(), id).await,
+    };
+    job_config.spaces.clear();
+    result?;
+    procedures(job_config, id).await
 }
 
-#[derive(Serialize)]
-pub struct FieldFormat {
-    name: &'static str,
-    r#type: &'static str,
-}
+/// Installs the stored procedures. They do not survive a restart, so the
+/// steps and the run using them install them again.
+pub async fn procedures(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_maintenance_connection();
 
-impl Tuple for FieldFormat {
-    fn encode_into_writer<W: std::io::Write>(
-        &self,
-        mut buf: W,
-    ) -> Result<(), tarantool_rs::errors::EncodingError> {
-        rmp_serde::encode::write_named(&mut buf, self)?;
-        Ok(())
+    if job_config.config.topology == Topology::Box {
+        conn.eval(PROCEDURES, ()).await?;
+        return Ok(());
     }
-}
-
-pub async fn vshard(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
-    let conn = job_config.connections.get_connection();
-
-    conn.eval(
-        r#"
-local service_registry = require("cartridge.service-registry")
-if service_registry.get("vshard-router") then
-    _G.vshard = require("vshard")
-end               
-return true
-            "#,
-        (),
-    )
-    .await
-    .and_then(|response| response.decode_result::<bool>())
-    .map(|created| {
-        if created {
-            println!("_G.vshard created")
-        } else {
-            println!("Error")
-        }
-    })?;
 
+    // Functions are not replicated, so procedures are installed on every
+    // replica to survive a master switch.
     conn.eval(
         r#"
-local create_primary, create_secondary = ...
+local procedures = ...
 local nb = require("net.box")
 local shards, err = vshard.router.routeall()
 if err then
     return nil, err
 end
 for _, shard in pairs(shards) do
-    local uri = shard.master.uri
-    local conn, err = nb.connect(uri)
-    if err then
-        return nil, err
-    end    
-
-    local _, err = conn:eval([[
-        local create_primary, create_secondary = ...
-        box.schema.create_space("ttbench_accounts", {
-            if_not_exists = true,
-            format = {
-                { name = "aid", type = "unsigned" },
-                { name = "abalance", type = "unsigned" },
-                { name = "bucket_id", type = "unsigned" },
-            }
-        })
-
-        if create_primary then
-            box.space.ttbench_accounts:create_index("primary", {
-                type = "hash",
-                unique = true,
-                if_not_exists = true,
-                parts = { "aid" }
-            })
+    for _, replica in pairs(shard.replicas) do
+        local conn, err = nb.connect(replica.uri)
+        if err then
+            return nil, err
         end
 
-        if create_secondary then
-            box.space.ttbench_accounts:create_index("abalance", {
-                type = "tree",
-                unique = false,
-                if_not_exists = true,
-                parts = { "abalance" }
-            })
+        local _, err = pcall(conn.eval, conn, procedures)
+        if err then
+            return nil, err
         end
-    ]], {create_primary, create_secondary})
-    if err then
-        return nil, err
     end
 end
 return true
         "#,
-        (
-            job_config.config.contains_step(&InitStep::Primary),
-            job_config.config.contains_step(&InitStep::Foreign),
-        ),
+        (PROCEDURES,),
     )
     .await
     .and_then(|response| response.decode_result::<bool>())?;
 
-    conn.eval(
-        r#"
-local create_primary, create_secondary = ...
-local nb = require("net.box")
-local shards, err = vshard.router.routeall()
-if err then
-    return nil, err
-end
-for _, shard in pairs(shards) do
-    local uri = shard.master.uri
-    local conn, err = nb.connect(uri)
-    if err then
-        return nil, err
-    end    
-
-    local _, err = conn:eval([[
-        local create_primary, create_secondary = ...
-        box.schema.create_space("ttbench_tellers", {
-            if_not_exists = true,
-            format = {
-                { name = "tid", type = "unsigned" },
-                { name = "tbalance", type = "unsigned" },
-                { name = "bucket_id", type = "unsigned" },
-            }
-        })
-
-        if create_primary then
-            box.space.ttbench_tellers:create_index("primary", {
-                type = "hash",
-                unique = true,
-                if_not_exists = true,
-                parts = { "tid" }
-            })
-        end
+    Ok(())
+}
 
-        if create_secondary then
-            box.space.ttbench_tellers:create_index("tbalance", {
-                type = "tree",
-                unique = false,
-                if_not_exists = true,
-                parts = { "tbalance" }
-            })
-        end
-    ]], {create_primary, create_secondary})
-    if err then
-        return nil, err
-    end
-end
-return true
-        "#,
-        (
-            job_config.config.contains_step(&InitStep::Primary),
-            job_config.config.contains_step(&InitStep::Foreign),
-        ),
-    )
-    .await
-    .and_then(|response| response.decode_result::<bool>())?;
+pub async fn vshard(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_maintenance_connection();
 
     conn.eval(
         r#"
-local create_primary, create_secondary = ...
+local spaces = ...
 local nb = require("net.box")
 local shards, err = vshard.router.routeall()
 if err then
@@ -191,140 +113,38 @@ for _, shard in pairs(shards) do
     local conn, err = nb.connect(uri)
     if err then
         return nil, err
-    end    
+    end
 
     local _, err = conn:eval([[
-        local create_primary, create_secondary = ...
-        box.schema.create_space("ttbench_branches", {
-            if_not_exists = true,
-            format = {
-                { name = "bid", type = "unsigned" },
-                { name = "bbalance", type = "unsigned" },
-                { name = "bucket_id", type = "unsigned" },
-            }
-        })
-
-        if create_primary then
-            box.space.ttbench_branches:create_index("primary", {
-                type = "hash",
-                unique = true,
-                if_not_exists = true,
-                parts = { "bid" }
-            })
-        end
-
-        if create_secondary then
-            box.space.ttbench_branches:create_index("bbalance", {
-                type = "tree",
-                unique = false,
-                if_not_exists = true,
-                parts = { "bbalance" }
-            })
+        local spaces = ...
+        for _, space in ipairs(spaces) do
+            box.schema.space.create(space.name, space.options)
         end
-    ]], {create_primary, create_secondary})
+    ]], {spaces})
     if err then
         return nil, err
     end
 end
 return true
         "#,
-        (
-            job_config.config.contains_step(&InitStep::Primary),
-            job_config.config.contains_step(&InitStep::Foreign),
-        ),
+        (named(&schema::spaces(&job_config.config))?,),
     )
     .await
     .and_then(|response| response.decode_result::<bool>())?;
 
-    conn.eval(
-        r#"
-local create_primary, create_secondary = ...
-local nb = require("net.box")
-local shards, err = vshard.router.routeall()
-if err then
-    return nil, err
-end
-for _, shard in pairs(shards) do
-    local uri = shard.master.uri
-    local conn, err = nb.connect(uri)
-    if err then
-        return nil, err
-    end    
-
-    local _, err = conn:eval([[
-        local create_primary, create_secondary = ...
-        box.schema.create_space("ttbench_history", {
-            if_not_exists = true,
-            format = {
-                { name = "uuid", type = "string" },
-                { name = "tid", type = "unsigned" },
-                { name = "bid", type = "unsigned" },
-                { name = "aid", type = "unsigned" },
-                { name = "delta", type = "unsigned" },
-                { name = "time", type = "unsigned" },
-                { name = "bucket_id", type = "unsigned" },
-            }
-        })
-
-        if create_primary then
-            box.space.ttbench_history:create_index("primary", {
-                type = "hash",
-                unique = true,
-                if_not_exists = true,
-                parts = { "uuid" }
-            })
-        end
-
-        if create_secondary then
-            box.space.ttbench_history:create_index("tid", {
-                type = "tree",
-                unique = false,
-                if_not_exists = true,
-                parts = { "tid" }
-            })
-
-            box.space.ttbench_history:create_index("bid", {
-                type = "tree",
-                unique = false,
-                if_not_exists = true,
-                parts = { "bid" }
-            })
-
-            box.space.ttbench_history:create_index("aid", {
-                type = "tree",
-                unique = false,
-                if_not_exists = true,
-                parts = { "aid" }
-            })
+    Ok(())
+}
 
-            box.space.ttbench_history:create_index("delta", {
-                type = "tree",
-                unique = false,
-                if_not_exists = true,
-                parts = { "delta" }
-            })
+pub async fn standalone(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_maintenance_connection();
 
-            box.space.ttbench_history:create_index("time", {
-                type = "tree",
-                unique = false,
-                if_not_exists = true,
-                parts = { "time" }
-            })
-        end
-    ]], {create_primary, create_secondary})
-    if err then
-        return nil, err
-    end
-end
-return true
-        "#,
-        (
-            job_config.config.contains_step(&InitStep::Primary),
-            job_config.config.contains_step(&InitStep::Foreign),
-        ),
-    )
-    .await
-    .and_then(|response| response.decode_result::<bool>())?;
+    for space in schema::spaces(&job_config.config) {
+        conn.eval(
+            "local name, options = ... box.schema.space.create(name, options)",
+            (space.name, named(&space.options)?),
+        )
+        .await?;
+    }
 
     Ok(())
 }
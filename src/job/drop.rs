@@ -2,65 +2,43 @@ use std::{pin::Pin, sync::Arc};
 
 use tarantool_rs::ExecutorExt;
 
-use crate::job::JobConfig;
+use crate::{config::Topology, job::JobConfig};
 
-pub async fn vshard(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
-    let conn = job_config.connections.get_connection();
-
-    conn.eval(
-        r#"
-local nb = require("net.box")
-local shards, err = vshard.router.routeall()
-if err then
-    return nil, err
-end
-for _, shard in pairs(shards) do
-    local uri = shard.master.uri
-    local conn, err = nb.connect(uri)
-    if err then
-        return nil, err
-    end    
+use super::schema::{ACCOUNTS, BRANCHES, HISTORY, TELLERS};
 
-    local _, err = conn:call("box.space.ttbench_accounts:drop")
-    if err then
-        return nil, err
+/// Drops the spaces which exist, so cleanup may be repeated.
+const DROP: &str = r#"
+local names = ...
+for _, name in ipairs(names) do
+    if box.space[name] ~= nil then
+        box.space[name]:drop()
     end
 end
-return true
-        "#,
-        (),
-    )
-    .await
-    .and_then(|response| response.decode_result::<bool>())?;
+"#;
 
-    conn.eval(
-        r#"
-local nb = require("net.box")
-local shards, err = vshard.router.routeall()
-if err then
-    return nil, err
-end
-for _, shard in pairs(shards) do
-    local uri = shard.master.uri
-    local conn, err = nb.connect(uri)
-    if err then
-        return nil, err
-    end    
+pub async fn run(job_config: Pin<Arc<JobConfig>>, id: u64) -> Result<(), anyhow::Error> {
+    let result = match job_config.config.topology {
+        Topology::Vshard => vshard(job_config.clone(), id).await,
+        Topology::Box => standalone(job_config.clone(), id).await,
+    };
+    job_config.spaces.clear();
+    result
+}
 
-    local _, err = conn:call("box.space.ttbench_tellers:drop")
-    if err then
-        return nil, err
-    end
-end
-return true
-        "#,
-        (),
-    )
-    .await
-    .and_then(|response| response.decode_result::<bool>())?;
+fn spaces(job_config: &JobConfig) -> Vec<&'static str> {
+    let mut spaces = vec![ACCOUNTS, TELLERS, BRANCHES];
+    if !job_config.config.keep_history {
+        spaces.push(HISTORY);
+    }
+    spaces
+}
+
+pub async fn vshard(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_maintenance_connection();
 
     conn.eval(
         r#"
+local drop, names = ...
 local nb = require("net.box")
 local shards, err = vshard.router.routeall()
 if err then
@@ -71,47 +49,29 @@ for _, shard in pairs(shards) do
     local conn, err = nb.connect(uri)
     if err then
         return nil, err
-    end    
+    end
 
-    local _, err = conn:call("box.space.ttbench_branches:drop")
+    local _, err = pcall(conn.eval, conn, drop, {names})
     if err then
         return nil, err
     end
 end
 return true
         "#,
-        (),
+        (DROP, spaces(&job_config)),
     )
     .await
     .and_then(|response| response.decode_result::<bool>())?;
 
-    if !job_config.config.keep_history {
-        conn.eval(
-            r#"
-local nb = require("net.box")
-local shards, err = vshard.router.routeall()
-if err then
-    return nil, err
-end
-for _, shard in pairs(shards) do
-    local uri = shard.master.uri
-    local conn, err = nb.connect(uri)
-    if err then
-        return nil, err
-    end    
+    Ok(())
+}
 
-    local _, err = conn:call("box.space.ttbench_history:drop")
-    if err then
-        return nil, err
-    end
-end
-return true
-        "#,
-            (),
-        )
-        .await
-        .and_then(|response| response.decode_result::<bool>())?;
-    }
+pub async fn standalone(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    job_config
+        .connections
+        .get_maintenance_connection()
+        .eval(DROP, (spaces(&job_config),))
+        .await?;
 
     Ok(())
 }
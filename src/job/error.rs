@@ -0,0 +1,246 @@
+use std::fmt::Display;
+
+use rmpv::Value;
+
+/// Box error codes, see `box.error`.
+const ER_NO_CONNECTION: u64 = 77;
+const ER_TIMEOUT: u64 = 78;
+const ER_TRANSACTION_CONFLICT: u64 = 97;
+
+/// Class of a failed transaction, which decides whether it is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The transaction was aborted by a concurrent one.
+    Conflict,
+    Timeout,
+    /// vshard `WRONG_BUCKET` or `TRANSFER_IS_IN_PROGRESS`, the bucket is
+    /// being moved by the rebalancer.
+    Routing,
+    ConnectionLost,
+    /// Anything else, which is not expected to go away on a retry.
+    Other,
+}
+
+impl ErrorClass {
+    pub const ALL: [ErrorClass; 5] = [
+        ErrorClass::Conflict,
+        ErrorClass::Timeout,
+        ErrorClass::Routing,
+        ErrorClass::ConnectionLost,
+        ErrorClass::Other,
+    ];
+
+    pub fn classify(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<tarantool_rs::Error>())
+            .map(Self::from_tarantool)
+            .unwrap_or(ErrorClass::Other)
+    }
+
+    fn from_tarantool(err: &tarantool_rs::Error) -> Self {
+        match err {
+            tarantool_rs::Error::Response(response) => Self::from_code(response.code.into()),
+            tarantool_rs::Error::CallEval(value) => Self::from_value(value),
+            tarantool_rs::Error::Timeout => ErrorClass::Timeout,
+            tarantool_rs::Error::Io(_) | tarantool_rs::Error::ConnectionClosed => {
+                ErrorClass::ConnectionLost
+            }
+            _ => ErrorClass::Other,
+        }
+    }
+
+    fn from_code(code: u64) -> Self {
+        match code {
+            ER_TRANSACTION_CONFLICT => ErrorClass::Conflict,
+            ER_TIMEOUT => ErrorClass::Timeout,
+            ER_NO_CONNECTION => ErrorClass::ConnectionLost,
+            _ => ErrorClass::Other,
+        }
+    }
+
+    /// Classifies an error returned by `vshard.router.call*`, a table with
+    /// `name` for sharding errors and `code` for box errors of a storage.
+    fn from_value(value: &Value) -> Self {
+        if field(value, "type").and_then(Value::as_str) == Some("ShardingError") {
+            return match field(value, "name").and_then(Value::as_str) {
+                Some("WRONG_BUCKET" | "TRANSFER_IS_IN_PROGRESS") => ErrorClass::Routing,
+                _ => ErrorClass::Other,
+            };
+        }
+        field(value, "code")
+            .and_then(Value::as_u64)
+            .map(Self::from_code)
+            .unwrap_or(ErrorClass::Other)
+    }
+
+    /// Conflicts and routing errors are raised before the commit. After a
+    /// timeout or a lost connection the commit may have been applied, and a
+    /// retry would apply the transaction twice.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ErrorClass::Conflict | ErrorClass::Routing)
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Key of an error for `--failures-detailed`: the box error code or the
+/// vshard error name when the server reported one.
+pub fn error_code(err: &anyhow::Error) -> String {
+    let Some(err) = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<tarantool_rs::Error>())
+    else {
+        return "client error".into();
+    };
+    match err {
+        tarantool_rs::Error::Response(response) => format!("box error {}", response.code),
+        tarantool_rs::Error::CallEval(value) => {
+            match (
+                field(value, "type").and_then(Value::as_str),
+                field(value, "name").and_then(Value::as_str),
+                field(value, "code").and_then(Value::as_u64),
+            ) {
+                (Some("ShardingError"), Some(name), _) => format!("vshard {name}"),
+                (_, _, Some(code)) => format!("box error {code}"),
+                _ => "call error".into(),
+            }
+        }
+        tarantool_rs::Error::Timeout => "request timeout".into(),
+        tarantool_rs::Error::Io(_) | tarantool_rs::Error::ConnectionClosed => {
+            "connection closed".into()
+        }
+        _ => "client error".into(),
+    }
+}
+
+/// Field of an error table returned by a call.
+fn field<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    value.as_map().and_then(|map| {
+        map.iter()
+            .find(|(name, _)| name.as_str() == Some(key))
+            .map(|(_, value)| value)
+    })
+}
+
+/// Index of the failed statement of a transaction, attached to its error as
+/// context.
+#[derive(Debug, Clone, Copy)]
+pub struct FailedStatement(pub usize);
+
+impl Display for FailedStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "statement {}", self.0)
+    }
+}
+
+impl Display for ErrorClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorClass::Conflict => write!(f, "transaction conflict"),
+            ErrorClass::Timeout => write!(f, "timeout"),
+            ErrorClass::Routing => write!(f, "bucket routing"),
+            ErrorClass::ConnectionLost => write!(f, "connection lost"),
+            ErrorClass::Other => write!(f, "other"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+    use rmpv::Value;
+    use tarantool_rs::{errors::ErrorResponse, Error};
+
+    use super::{error_code, ErrorClass, FailedStatement};
+
+    fn vshard_error(fields: &[(&str, Value)]) -> anyhow::Error {
+        Error::CallEval(Value::Map(
+            fields
+                .iter()
+                .map(|(key, value)| ((*key).into(), value.clone()))
+                .collect(),
+        ))
+        .into()
+    }
+
+    #[test]
+    fn test_classify() {
+        let conflict =
+            ErrorResponse::new(97, "Transaction has been aborted by conflict".into(), None);
+        assert_eq!(
+            ErrorClass::classify(&Error::Response(conflict).into()),
+            ErrorClass::Conflict
+        );
+        assert_eq!(
+            ErrorClass::classify(&Error::Timeout.into()),
+            ErrorClass::Timeout
+        );
+        assert_eq!(
+            ErrorClass::classify(&Error::ConnectionClosed.into()),
+            ErrorClass::ConnectionLost
+        );
+        assert_eq!(
+            ErrorClass::classify(&vshard_error(&[
+                ("type", "ShardingError".into()),
+                ("code", 1.into()),
+                ("name", "WRONG_BUCKET".into()),
+            ])),
+            ErrorClass::Routing
+        );
+        assert_eq!(
+            ErrorClass::classify(&vshard_error(&[
+                ("type", "ClientError".into()),
+                ("code", 97.into()),
+            ])),
+            ErrorClass::Conflict
+        );
+        assert_eq!(
+            ErrorClass::classify(&vshard_error(&[
+                ("type", "ShardingError".into()),
+                ("code", 6.into()),
+                ("name", "MISSING_MASTER".into()),
+            ])),
+            ErrorClass::Other
+        );
+        assert_eq!(
+            ErrorClass::classify(&anyhow!("space 'ttbench_accounts' not found")),
+            ErrorClass::Other
+        );
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(ErrorClass::Conflict.is_retryable());
+        assert!(ErrorClass::Routing.is_retryable());
+        assert!(!ErrorClass::Timeout.is_retryable());
+        assert!(!ErrorClass::ConnectionLost.is_retryable());
+        assert!(!ErrorClass::Other.is_retryable());
+    }
+
+    #[test]
+    fn test_error_code() {
+        let conflict = ErrorResponse::new(97, "conflict".into(), None);
+        assert_eq!(
+            error_code(&Error::Response(conflict).into()),
+            "box error 97"
+        );
+        assert_eq!(
+            error_code(&vshard_error(&[
+                ("type", "ShardingError".into()),
+                ("code", 7.into()),
+                ("name", "TRANSFER_IS_IN_PROGRESS".into()),
+            ])),
+            "vshard TRANSFER_IS_IN_PROGRESS"
+        );
+        assert_eq!(
+            error_code(&Error::Timeout.into()).as_str(),
+            "request timeout"
+        );
+
+        let err = anyhow::Error::from(Error::Timeout).context(FailedStatement(3));
+        assert_eq!(err.downcast_ref::<FailedStatement>().unwrap().0, 3);
+        assert_eq!(ErrorClass::classify(&err), ErrorClass::Timeout);
+    }
+}
@@ -0,0 +1,291 @@
+use std::{collections::HashMap, pin::Pin, sync::Arc};
+
+use anyhow::anyhow;
+use tarantool_rs::ExecutorExt;
+
+use crate::config::{Config, Topology, TpcbMode, ACCOUNTS_PER_BRANCH, TELLERS_PER_BRANCH};
+
+use super::{
+    schema::{Balance, ACCOUNTS, BRANCHES, TELLERS},
+    JobConfig,
+};
+
+/// Number of ids each server-side generation request covers.
+pub const CHUNK_SIZE: u64 = 100_000;
+/// Number of tuples inserted by storages in one transaction.
+const COMMIT_SIZE: u64 = 1000;
+
+/// Inserts the tuples of `from..to` with zero balance. With a bucket count
+/// only tuples of the buckets active on the instance are inserted, so every
+/// storage master may be given the same range. Ids are 64-bit integers, as
+/// doubles lose precision above 2^53.
+const GENERATE: &str = r#"
+local space, from, to, divisor, bucket_count, commit_size = ...
+local digest = require("digest")
+from, to, divisor = tonumber64(from), tonumber64(to), tonumber64(divisor)
+local rows = 0
+box.begin()
+local id = from
+while id < to do
+    local tuple = {id, 0}
+    local owned = true
+    if bucket_count > 0 then
+        local key = string.format("%d", id / divisor)
+        local bucket_id = digest.crc32(key) % bucket_count + 1
+        local bucket = box.space._bucket:get(bucket_id)
+        owned = bucket ~= nil and bucket.status == "active"
+        tuple[3] = bucket_id
+    end
+    if owned then
+        box.space[space]:replace(tuple)
+        rows = rows + 1
+        if rows % commit_size == 0 then
+            box.commit()
+            box.begin()
+        end
+    end
+    id = id + 1
+end
+box.commit()
+return rows
+"#;
+
+/// Spaces filled by the GenerateData steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Accounts,
+    Tellers,
+    Branches,
+}
+
+impl Table {
+    pub const ALL: [Table; 3] = [Table::Accounts, Table::Tellers, Table::Branches];
+
+    pub fn space(&self) -> &'static str {
+        match self {
+            Table::Accounts => ACCOUNTS,
+            Table::Tellers => TELLERS,
+            Table::Branches => BRANCHES,
+        }
+    }
+
+    pub fn rows(&self, config: &Config) -> u64 {
+        match self {
+            Table::Accounts => config.test_config.ttbench_accounts,
+            Table::Tellers => config.test_config.ttbench_tellers,
+            Table::Branches => config.test_config.ttbench_branches,
+        }
+    }
+
+    /// Tuples are sharded by `id / divisor`, see `TpcbMode::Server`.
+    fn divisor(&self, config: &Config) -> u64 {
+        match (self, config.tpcb_mode) {
+            (Table::Accounts, TpcbMode::Server) => ACCOUNTS_PER_BRANCH,
+            (Table::Tellers, TpcbMode::Server) => TELLERS_PER_BRANCH,
+            _ => 1,
+        }
+    }
+
+    fn bucket_id(&self, config: &Config, id: u64) -> u32 {
+        match self {
+            Table::Accounts => config.account_bucket_id(id),
+            Table::Tellers => config.teller_bucket_id(id),
+            Table::Branches => config.branch_bucket_id(id),
+        }
+    }
+
+    pub fn chunks(&self, config: &Config) -> u64 {
+        self.rows(config).div_ceil(CHUNK_SIZE)
+    }
+
+    pub fn batches(&self, config: &Config) -> u64 {
+        self.rows(config).div_ceil(config.init_batch_size)
+    }
+}
+
+/// Replicaset of every bucket, so that a batch is split into one call per
+/// replicaset.
+#[derive(Default)]
+pub struct Routes(Vec<Option<usize>>);
+
+impl Routes {
+    /// Asks storage masters for their active buckets.
+    pub async fn fetch(job_config: &JobConfig) -> Result<Self, anyhow::Error> {
+        let replicasets = job_config
+            .connections
+            .get_maintenance_connection()
+            .eval(
+                r#"
+local nb = require("net.box")
+local shards, err = vshard.router.routeall()
+if err then
+    return nil, err
+end
+local replicasets = {}
+for _, shard in pairs(shards) do
+    local conn, err = nb.connect(shard.master.uri)
+    if err then
+        return nil, err
+    end
+    local ok, buckets = pcall(conn.eval, conn, [[
+        local buckets = {}
+        for _, bucket in box.space._bucket.index.status:pairs({"active"}) do
+            table.insert(buckets, bucket.id)
+        end
+        return buckets
+    ]])
+    conn:close()
+    if not ok then
+        return nil, buckets
+    end
+    table.insert(replicasets, buckets)
+end
+return replicasets
+                "#,
+                (),
+            )
+            .await
+            .and_then(|response| response.decode_result::<Vec<Vec<u32>>>())?;
+
+        let mut routes = vec![None; job_config.config.bucket_count as usize + 1];
+        for (replicaset, buckets) in replicasets.into_iter().enumerate() {
+            for bucket_id in buckets {
+                if let Some(route) = routes.get_mut(bucket_id as usize) {
+                    *route = Some(replicaset);
+                }
+            }
+        }
+        Ok(Self(routes))
+    }
+
+    fn replicaset(&self, bucket_id: u32) -> Result<usize, anyhow::Error> {
+        self.0
+            .get(bucket_id as usize)
+            .copied()
+            .flatten()
+            .ok_or_else(|| anyhow!("bucket {bucket_id} is not active on any replicaset"))
+    }
+}
+
+/// Generates the tuples of a chunk of ids on the instances.
+pub async fn server(
+    job_config: Pin<Arc<JobConfig>>,
+    table: Table,
+    chunk: u64,
+) -> Result<(), anyhow::Error> {
+    let config = &job_config.config;
+    let from = chunk * CHUNK_SIZE;
+    let to = (from + CHUNK_SIZE).min(table.rows(config));
+    let conn = job_config.connections.get_maintenance_connection();
+
+    let rows = match config.topology {
+        Topology::Vshard => {
+            conn.eval(
+                r#"
+local generate, args = ...
+local nb = require("net.box")
+local shards, err = vshard.router.routeall()
+if err then
+    return nil, err
+end
+local futures = {}
+for _, shard in pairs(shards) do
+    local conn, err = nb.connect(shard.master.uri)
+    if err then
+        return nil, err
+    end
+    table.insert(futures, {conn, conn:eval(generate, args, {is_async = true})})
+end
+local rows = 0
+for _, future in ipairs(futures) do
+    local conn, future = unpack(future)
+    local result, err = future:wait_result()
+    conn:close()
+    if err then
+        return nil, tostring(err)
+    end
+    rows = rows + result[1]
+end
+return rows
+                "#,
+                (
+                    GENERATE,
+                    (
+                        table.space(),
+                        from,
+                        to,
+                        table.divisor(config),
+                        config.bucket_count,
+                        COMMIT_SIZE,
+                    ),
+                ),
+            )
+            .await
+        }
+        Topology::Box => {
+            conn.eval(GENERATE, (table.space(), from, to, 1, 0, COMMIT_SIZE))
+                .await
+        }
+    }
+    .and_then(|response| response.decode_result::<u64>())?;
+
+    // Buckets being moved are not active anywhere.
+    if rows != to - from {
+        return Err(anyhow!(
+            "{}: {rows} of {} tuples generated for ids {from}..{to}",
+            table.space(),
+            to - from
+        ));
+    }
+
+    Ok(())
+}
+
+/// Sends a batch of tuples generated by the client, one call per
+/// replicaset.
+pub async fn client(
+    job_config: Pin<Arc<JobConfig>>,
+    table: Table,
+    routes: Arc<Routes>,
+    batch: u64,
+) -> Result<(), anyhow::Error> {
+    let config = &job_config.config;
+    let from = batch * config.init_batch_size;
+    let to = (from + config.init_batch_size).min(table.rows(config));
+    let conn = job_config.connections.get_connection();
+
+    if config.topology == Topology::Box {
+        let tuples: Vec<_> = (from..to).map(|id| (id, 0)).collect();
+        conn.call("ttbench_replace_batch", (table.space(), tuples))
+            .await
+            .and_then(|response| response.decode_result::<u64>())?;
+        return Ok(());
+    }
+
+    let mut replicasets: HashMap<usize, Vec<Balance>> = HashMap::new();
+    for id in from..to {
+        let bucket_id = table.bucket_id(config, id);
+        replicasets
+            .entry(routes.replicaset(bucket_id)?)
+            .or_default()
+            .push(Balance {
+                id,
+                balance: 0,
+                bucket_id,
+            });
+    }
+    for tuples in replicasets.into_values() {
+        conn.call(
+            "vshard.router.callrw",
+            (
+                tuples[0].bucket_id,
+                "ttbench_replace_batch",
+                (table.space(), tuples),
+            ),
+        )
+        .await
+        .and_then(|response| response.decode_result::<u64>())?;
+    }
+
+    Ok(())
+}
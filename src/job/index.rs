@@ -0,0 +1,80 @@
+use std::{pin::Pin, sync::Arc};
+
+use tarantool_rs::ExecutorExt;
+
+use crate::config::Topology;
+
+use super::{
+    schema::{self, named, Index},
+    JobConfig,
+};
+
+const CREATE_INDEXES: &str = r#"
+local indexes = ...
+for _, index in ipairs(indexes) do
+    box.space[index.space]:create_index(index.name, index.options)
+end
+"#;
+
+/// Creates primary indexes, which are required before the data is loaded.
+pub async fn primary(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    let indexes = schema::spaces(&job_config.config)
+        .into_iter()
+        .map(|space| space.primary)
+        .collect();
+    create(&job_config, indexes).await
+}
+
+/// Creates secondary indexes, which is faster after the data is loaded.
+pub async fn foreign(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    let indexes = schema::spaces(&job_config.config)
+        .into_iter()
+        .flat_map(|space| space.secondary)
+        .collect();
+    create(&job_config, indexes).await
+}
+
+async fn create(job_config: &JobConfig, indexes: Vec<Index>) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_maintenance_connection();
+    let indexes = named(&indexes)?;
+
+    match job_config.config.topology {
+        Topology::Vshard => {
+            conn.eval(
+                r#"
+local create_indexes, indexes = ...
+local nb = require("net.box")
+local shards, err = vshard.router.routeall()
+if err then
+    return nil, err
+end
+local futures = {}
+for _, shard in pairs(shards) do
+    local conn, err = nb.connect(shard.master.uri)
+    if err then
+        return nil, err
+    end
+    table.insert(futures, {conn, conn:eval(create_indexes, {indexes}, {is_async = true})})
+end
+for _, future in ipairs(futures) do
+    local conn, future = unpack(future)
+    local _, err = future:wait_result()
+    conn:close()
+    if err then
+        return nil, tostring(err)
+    end
+end
+return true
+                "#,
+                (CREATE_INDEXES, indexes),
+            )
+            .await
+            .and_then(|response| response.decode_result::<bool>())?;
+        }
+        Topology::Box => {
+            conn.eval(CREATE_INDEXES, (indexes,)).await?;
+        }
+    }
+
+    Ok(())
+}
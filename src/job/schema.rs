@@ -0,0 +1,232 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use anyhow::anyhow;
+use rmpv::Value;
+use serde::{Deserialize, Serialize};
+use tarantool_rs::{Executor, ExecutorExt};
+
+use crate::config::{Config, Engine, IndexType, Topology};
+
+pub const ACCOUNTS: &str = "ttbench_accounts";
+pub const TELLERS: &str = "ttbench_tellers";
+pub const BRANCHES: &str = "ttbench_branches";
+pub const HISTORY: &str = "ttbench_history";
+
+/// Tuple of accounts, tellers and branches in the vshard topology.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Balance {
+    pub id: u64,
+    pub balance: i64,
+    pub bucket_id: u32,
+}
+
+/// Space, passed to `box.schema.space.create` as is. Indexes are created by
+/// separate init steps.
+#[derive(Serialize)]
+pub struct Space {
+    pub name: &'static str,
+    pub options: CreateSpaceOptions,
+    #[serde(skip)]
+    pub primary: Index,
+    #[serde(skip)]
+    pub secondary: Vec<Index>,
+}
+
+#[derive(Serialize)]
+pub struct CreateSpaceOptions {
+    engine: &'static str,
+    if_not_exists: bool,
+    format: Vec<FieldFormat>,
+}
+
+#[derive(Serialize)]
+pub struct FieldFormat {
+    name: &'static str,
+    r#type: &'static str,
+}
+
+/// Index, passed to `space:create_index` as is.
+#[derive(Serialize)]
+pub struct Index {
+    pub space: &'static str,
+    pub name: &'static str,
+    pub options: IndexOptions,
+}
+
+#[derive(Serialize)]
+pub struct IndexOptions {
+    r#type: &'static str,
+    unique: bool,
+    if_not_exists: bool,
+    parts: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    range_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_count_per_level: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bloom_fpr: Option<f64>,
+}
+
+impl IndexOptions {
+    fn new(config: &Config, r#type: IndexType, unique: bool, part: &'static str) -> Self {
+        let vinyl = match config.engine {
+            Engine::Vinyl => config.vinyl.clone(),
+            Engine::Memtx => Default::default(),
+        };
+        Self {
+            r#type: r#type.name(),
+            unique,
+            if_not_exists: true,
+            parts: vec![part],
+            page_size: vinyl.page_size,
+            range_size: vinyl.range_size,
+            run_count_per_level: vinyl.run_count_per_level,
+            bloom_fpr: vinyl.bloom_fpr,
+        }
+    }
+}
+
+/// Spaces of the benchmark. The first field is the primary key, secondary
+/// indexes are built over a single field each.
+pub fn spaces(config: &Config) -> Vec<Space> {
+    let id = config.id_type.name();
+    vec![
+        space(
+            config,
+            ACCOUNTS,
+            &[("aid", id), ("abalance", "integer")],
+            &["abalance"],
+        ),
+        space(
+            config,
+            TELLERS,
+            &[("tid", id), ("tbalance", "integer")],
+            &["tbalance"],
+        ),
+        space(
+            config,
+            BRANCHES,
+            &[("bid", id), ("bbalance", "integer")],
+            &["bbalance"],
+        ),
+        space(
+            config,
+            HISTORY,
+            &[
+                ("uuid", "string"),
+                ("tid", id),
+                ("bid", id),
+                ("aid", id),
+                ("delta", "integer"),
+                ("time", "unsigned"),
+            ],
+            &["tid", "bid", "aid", "delta", "time"],
+        ),
+    ]
+}
+
+fn space(
+    config: &Config,
+    name: &'static str,
+    fields: &[(&'static str, &'static str)],
+    secondary: &[&'static str],
+) -> Space {
+    let mut format: Vec<_> = fields
+        .iter()
+        .map(|&(name, r#type)| FieldFormat { name, r#type })
+        .collect();
+    if config.topology == Topology::Vshard {
+        format.push(FieldFormat {
+            name: "bucket_id",
+            r#type: "unsigned",
+        });
+    }
+
+    Space {
+        name,
+        options: CreateSpaceOptions {
+            engine: config.engine.name(),
+            if_not_exists: true,
+            format,
+        },
+        primary: Index {
+            space: name,
+            name: "primary",
+            options: IndexOptions::new(config, config.primary_index, true, fields[0].0),
+        },
+        secondary: secondary
+            .iter()
+            .map(|&part| Index {
+                space: name,
+                name: part,
+                options: IndexOptions::new(config, IndexType::Tree, false, part),
+            })
+            .collect(),
+    }
+}
+
+/// Encodes structs as MessagePack maps, which is what Lua options expect.
+pub fn named<T: Serialize>(value: &T) -> Result<Value, anyhow::Error> {
+    let encoded = rmp_serde::to_vec_named(value)?;
+    Ok(rmpv::decode::read_value(&mut encoded.as_slice())?)
+}
+
+/// Ids of the spaces for native requests of the builtins and scripts,
+/// resolved on first use.
+#[derive(Default)]
+pub struct SpaceIds(Mutex<HashMap<String, u32>>);
+
+impl SpaceIds {
+    pub async fn get<E: Executor>(&self, executor: &E, name: &str) -> Result<u32, anyhow::Error> {
+        if let Some(id) = self.0.lock().unwrap().get(name) {
+            return Ok(*id);
+        }
+        let id = executor
+            .space(name)
+            .await?
+            .ok_or_else(|| anyhow!("space '{name}' not found"))?
+            .metadata()
+            .id();
+        self.0.lock().unwrap().insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    /// Forgets the ids after the spaces are recreated.
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rmpv::Value;
+
+    use super::{named, IndexOptions};
+
+    #[test]
+    fn test_named() {
+        let options = IndexOptions {
+            r#type: "tree",
+            unique: false,
+            if_not_exists: true,
+            parts: vec!["tid"],
+            page_size: Some(8192),
+            range_size: None,
+            run_count_per_level: None,
+            bloom_fpr: None,
+        };
+
+        assert_eq!(
+            named(&options).unwrap(),
+            Value::Map(vec![
+                ("type".into(), "tree".into()),
+                ("unique".into(), false.into()),
+                ("if_not_exists".into(), true.into()),
+                ("parts".into(), Value::Array(vec!["tid".into()])),
+                ("page_size".into(), 8192.into()),
+            ])
+        );
+    }
+}
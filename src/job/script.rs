@@ -0,0 +1,129 @@
+use std::{fmt::Debug, pin::Pin, sync::Arc};
+
+use rmpv::Value;
+use tarantool_rs::{Executor, ExecutorExt};
+
+use crate::{
+    job::{schema::SpaceIds, JobConfig},
+    script::{Script, Statement},
+    status::statements::Recorder,
+};
+
+#[derive(Clone)]
+pub struct ScriptRun {
+    pub script: Arc<Script>,
+    pub values: Vec<Value>,
+}
+
+impl Debug for ScriptRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptRun")
+            .field("script", &self.script.name)
+            .field("values", &self.values)
+            .finish()
+    }
+}
+
+pub async fn run(job_config: Pin<Arc<JobConfig>>, value: ScriptRun) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_connection();
+
+    let mut statements = job_config.statements.transaction(&value.script.name);
+
+    if value.script.transaction {
+        let transaction = statements.run("begin", conn.transaction()).await?;
+        execute(&transaction, &job_config.spaces, &value, &mut statements).await?;
+        statements.run("commit", transaction.commit()).await?;
+    } else {
+        execute(conn, &job_config.spaces, &value, &mut statements).await?;
+    }
+    Ok(())
+}
+
+async fn execute<E: Executor>(
+    executor: &E,
+    spaces: &SpaceIds,
+    value: &ScriptRun,
+    statements: &mut Recorder<'_>,
+) -> Result<(), anyhow::Error> {
+    for statement in &value.script.statements {
+        statements
+            .run(
+                statement,
+                execute_statement(executor, spaces, value, statement),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn execute_statement<E: Executor>(
+    executor: &E,
+    spaces: &SpaceIds,
+    value: &ScriptRun,
+    statement: &Statement,
+) -> Result<(), anyhow::Error> {
+    let values = &value.values;
+    match statement {
+        Statement::Call { function, args } => {
+            executor
+                .call(function, args.render_args(values))
+                .await
+                .and_then(|response| response.decode_result::<Value>())?;
+        }
+        Statement::Eval { expr, args } => {
+            executor
+                .eval(expr, args.render_args(values))
+                .await
+                .and_then(|response| response.decode_result::<Value>())?;
+        }
+        Statement::Select {
+            space,
+            index,
+            limit,
+            key,
+        } => {
+            let space_id = spaces.get(executor, space).await?;
+            executor
+                .select::<Value, _>(
+                    space_id,
+                    *index,
+                    *limit,
+                    None,
+                    None,
+                    key.render_args(values),
+                )
+                .await?;
+        }
+        Statement::Insert { space, tuple } => {
+            let space_id = spaces.get(executor, space).await?;
+            executor.insert(space_id, tuple.render_args(values)).await?;
+        }
+        Statement::Replace { space, tuple } => {
+            let space_id = spaces.get(executor, space).await?;
+            executor
+                .replace(space_id, tuple.render_args(values))
+                .await?;
+        }
+        Statement::Update {
+            space,
+            key,
+            operations,
+        } => {
+            let space_id = spaces.get(executor, space).await?;
+            executor
+                .update(
+                    space_id,
+                    0,
+                    key.render_args(values),
+                    operations.render_args(values),
+                )
+                .await?;
+        }
+        Statement::Execute { query, binds } => {
+            executor
+                .execute_sql(query, binds.render_args(values))
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::{pin::Pin, sync::Arc};
+
+use tarantool_rs::ExecutorExt;
+
+use crate::job::JobConfig;
+
+use super::{
+    schema::{Balance, ACCOUNTS},
+    tpcb::Transaction,
+    workload::Builtin,
+};
+
+/// Reads the balance of a random account.
+pub async fn vshard(
+    job_config: Pin<Arc<JobConfig>>,
+    value: Transaction,
+) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_connection();
+
+    let aid_bucket_id = job_config.config.account_bucket_id(value.aid);
+    job_config
+        .statements
+        .transaction(Builtin::SelectOnly.name())
+        .run("callbro accounts:get", async {
+            conn.call(
+                "vshard.router.callbro",
+                (
+                    aid_bucket_id,
+                    "box.space.ttbench_accounts:get",
+                    (value.aid,),
+                ),
+            )
+            .await
+            .and_then(|response| response.decode_result::<Balance>())
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn standalone(
+    job_config: Pin<Arc<JobConfig>>,
+    value: Transaction,
+) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_connection();
+    let accounts = job_config.spaces.get(conn, ACCOUNTS).await?;
+
+    job_config
+        .statements
+        .transaction(Builtin::SelectOnly.name())
+        .run(
+            "select accounts",
+            conn.select::<(u64, i64), _>(accounts, 0, None, None, None, (value.aid,)),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use std::{pin::Pin, sync::Arc};
+
+use tarantool_rs::Executor;
+
+use crate::job::JobConfig;
+
+use super::{
+    schema::{ACCOUNTS, HISTORY},
+    tpcb::{
+        get_account, insert_history, insert_history_local, select_account_local, update_account,
+        update_account_local, Transaction,
+    },
+    workload::Builtin,
+};
+
+/// TPC-B like transaction without updates of tellers and branches.
+pub async fn vshard(
+    job_config: Pin<Arc<JobConfig>>,
+    value: Transaction,
+) -> Result<(), anyhow::Error> {
+    let mut statements = job_config
+        .statements
+        .transaction(Builtin::SimpleUpdate.name());
+    let transaction = statements
+        .run(
+            "begin",
+            job_config.connections.get_connection().transaction(),
+        )
+        .await?;
+
+    update_account(&mut statements, &transaction, &job_config.config, &value).await?;
+    get_account(&mut statements, &transaction, &job_config.config, &value).await?;
+
+    insert_history(&mut statements, &transaction, &job_config.config, value).await?;
+
+    statements.run("commit", transaction.commit()).await?;
+    Ok(())
+}
+
+pub async fn standalone(
+    job_config: Pin<Arc<JobConfig>>,
+    value: Transaction,
+) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_connection();
+    let accounts = job_config.spaces.get(conn, ACCOUNTS).await?;
+    let history = job_config.spaces.get(conn, HISTORY).await?;
+    let mut statements = job_config
+        .statements
+        .transaction(Builtin::SimpleUpdate.name());
+    let transaction = statements.run("begin", conn.transaction()).await?;
+
+    update_account_local(&mut statements, &transaction, accounts, &value).await?;
+    select_account_local(&mut statements, &transaction, accounts, &value).await?;
+
+    insert_history_local(&mut statements, &transaction, history, value).await?;
+
+    statements.run("commit", transaction.commit()).await?;
+    Ok(())
+}
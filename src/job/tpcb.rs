@@ -1,40 +1,72 @@
 use std::{
-    ops::SubAssign,
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use tarantool_rs::{Executor, ExecutorExt};
+use tarantool_rs::{DmoResponse, Executor, ExecutorExt};
 use uuid::Uuid;
 
 use crate::{
-    config::Config,
+    config::{Config, TpcbMode, ACCOUNTS_PER_BRANCH, TELLERS_PER_BRANCH},
     job::JobConfig,
-    status::{iterations::Iterations, Target},
+    status::statements::Recorder,
     utils::calculate_bucket_id,
 };
 
-use super::{accounts::Account, branches::Branch, tellers::Teller};
+use super::{
+    schema::{Balance, ACCOUNTS, BRANCHES, HISTORY, TELLERS},
+    workload::Builtin,
+};
 
 pub struct TransactionGenerator {
     max_aid: u64,
     max_tid: u64,
     max_bid: u64,
-    max_delta: u64,
+    delta_min: i64,
+    delta_max: i64,
+    mode: TpcbMode,
 }
 
 impl TransactionGenerator {
+    pub fn new(config: &Config) -> Self {
+        TransactionGenerator {
+            max_aid: config.test_config.ttbench_accounts,
+            max_tid: config.test_config.ttbench_tellers,
+            max_bid: config.test_config.ttbench_branches,
+            delta_min: config.delta_min,
+            delta_max: config.delta_max,
+            mode: config.tpcb_mode,
+        }
+    }
+
     pub fn generate(&self) -> Transaction {
         let mut rng = rand::thread_rng();
+        let (aid, tid, bid) = match self.mode {
+            TpcbMode::Client => (
+                rng.gen_range(0..self.max_aid),
+                rng.gen_range(0..self.max_tid),
+                rng.gen_range(0..self.max_bid),
+            ),
+            // The stored procedure can only reach rows of its own storage,
+            // so the teller and the account are taken from the branch.
+            TpcbMode::Server => {
+                let bid = rng.gen_range(0..self.max_bid);
+                (
+                    bid * ACCOUNTS_PER_BRANCH + rng.gen_range(0..ACCOUNTS_PER_BRANCH),
+                    bid * TELLERS_PER_BRANCH + rng.gen_range(0..TELLERS_PER_BRANCH),
+                    bid,
+                )
+            }
+        };
         Transaction {
             uuid: Uuid::new_v4().to_string(),
-            aid: rng.gen_range(0..self.max_aid),
-            tid: rng.gen_range(0..self.max_tid),
-            bid: rng.gen_range(0..self.max_bid),
-            delta: rng.gen_range(0..self.max_delta),
+            aid,
+            tid,
+            bid,
+            delta: rng.gen_range(self.delta_min..=self.delta_max),
             time: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .expect("Time went backwards")
@@ -44,125 +76,308 @@ impl TransactionGenerator {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Transaction {
-    uuid: String,
-    tid: u64,
-    bid: u64,
-    aid: u64,
-    delta: u64,
-    time: u64,
-    bucket_id: u32,
+    pub uuid: String,
+    pub tid: u64,
+    pub bid: u64,
+    pub aid: u64,
+    pub delta: i64,
+    pub time: u64,
+    pub bucket_id: u32,
 }
 
-impl Target for Iterations<TransactionGenerator> {
-    type Output = Transaction;
-
-    fn new(config: &Config) -> Self {
-        Iterations {
-            generator: TransactionGenerator {
-                max_aid: config.test_config.ttbench_accounts,
-                max_tid: config.test_config.ttbench_tellers,
-                max_bid: config.test_config.ttbench_branches,
-                // TODO Решить какой именно будет диапазон у дельты
-                max_delta: 1000,
-            },
-            counter: Arc::new(Mutex::new(0)),
-        }
-    }
+pub async fn vshard(
+    job_config: Pin<Arc<JobConfig>>,
+    value: Transaction,
+) -> Result<(), anyhow::Error> {
+    let mut statements = job_config.statements.transaction(Builtin::TpcbLike.name());
+    let transaction = statements
+        .run(
+            "begin",
+            job_config.connections.get_connection().transaction(),
+        )
+        .await?;
 
-    fn make_progress(&self) -> Result<Option<Self::Output>, anyhow::Error> {
-        let mut locked = self.counter.lock().unwrap();
-        if locked.eq(&0) {
-            return Ok(None);
-        }
-        locked.sub_assign(1);
-        Ok(Some(self.generator.generate()))
-    }
+    update_account(&mut statements, &transaction, &job_config.config, &value).await?;
+    get_account(&mut statements, &transaction, &job_config.config, &value).await?;
 
-    fn is_reached(&self) -> Result<bool, anyhow::Error> {
-        let locked = self.counter.lock().unwrap();
-        if locked.eq(&0) {
-            return Ok(true);
-        }
-        Ok(false)
-    }
+    let tid_bucket_id = job_config.config.teller_bucket_id(value.tid);
+    statements
+        .run("callrw tellers:update", async {
+            transaction
+                .call(
+                    "vshard.router.callrw",
+                    (
+                        tid_bucket_id,
+                        "box.space.ttbench_tellers:update",
+                        (value.tid, (("+", "tbalance", value.delta),)),
+                    ),
+                )
+                .await
+                .and_then(|response| response.decode_result::<Balance>())
+        })
+        .await?;
+
+    let bid_bucket_id = job_config.config.branch_bucket_id(value.bid);
+    statements
+        .run("callrw branches:update", async {
+            transaction
+                .call(
+                    "vshard.router.callrw",
+                    (
+                        bid_bucket_id,
+                        "box.space.ttbench_branches:update",
+                        (value.bid, (("+", "bbalance", value.delta),)),
+                    ),
+                )
+                .await
+                .and_then(|response| response.decode_result::<Balance>())
+        })
+        .await?;
+
+    insert_history(&mut statements, &transaction, &job_config.config, value).await?;
+
+    statements.run("commit", transaction.commit()).await?;
+    Ok(())
 }
 
-pub async fn vshard(
+/// Executes the whole transaction with a single call of the `tpcb_like`
+/// stored procedure on the storage of the branch.
+pub async fn vshard_server(
     job_config: Pin<Arc<JobConfig>>,
     value: Transaction,
 ) -> Result<(), anyhow::Error> {
-    let transaction = job_config
-        .connections
-        .get_connection()
-        .transaction()
+    let bucket_id = job_config.config.branch_bucket_id(value.bid);
+    job_config
+        .statements
+        .transaction(Builtin::TpcbLike.name())
+        .run("callrw tpcb_like", async {
+            job_config
+                .connections
+                .get_connection()
+                .call(
+                    "vshard.router.callrw",
+                    (
+                        bucket_id,
+                        "tpcb_like",
+                        (
+                            value.aid,
+                            value.tid,
+                            value.bid,
+                            value.delta,
+                            value.uuid,
+                            value.time,
+                            bucket_id,
+                        ),
+                    ),
+                )
+                .await
+                .and_then(|response| response.decode_result::<u64>())
+        })
         .await?;
 
-    let aid_bucket_id = calculate_bucket_id(value.aid.to_string(), job_config.config.bucket_count);
-    transaction
-        .call(
-            "vshard.router.callrw",
-            (
-                aid_bucket_id,
-                "box.space.ttbench_accounts:update",
-                (value.aid, (("+", "abalance", value.delta),)),
-            ),
-        )
+    Ok(())
+}
+
+pub async fn standalone(
+    job_config: Pin<Arc<JobConfig>>,
+    value: Transaction,
+) -> Result<(), anyhow::Error> {
+    let conn = job_config.connections.get_connection();
+    let accounts = job_config.spaces.get(conn, ACCOUNTS).await?;
+    let tellers = job_config.spaces.get(conn, TELLERS).await?;
+    let branches = job_config.spaces.get(conn, BRANCHES).await?;
+    let history = job_config.spaces.get(conn, HISTORY).await?;
+    let mut statements = job_config.statements.transaction(Builtin::TpcbLike.name());
+    let transaction = statements.run("begin", conn.transaction()).await?;
+
+    update_account_local(&mut statements, &transaction, accounts, &value).await?;
+    select_account_local(&mut statements, &transaction, accounts, &value).await?;
+
+    statements
+        .run("update tellers", async {
+            transaction
+                .update(tellers, 0, (value.tid,), (("+", "tbalance", value.delta),))
+                .await?
+                .decode::<(u64, i64)>()
+                .map_err(tarantool_rs::Error::from)
+        })
+        .await?;
+
+    statements
+        .run("update branches", async {
+            transaction
+                .update(branches, 0, (value.bid,), (("+", "bbalance", value.delta),))
+                .await?
+                .decode::<(u64, i64)>()
+                .map_err(tarantool_rs::Error::from)
+        })
+        .await?;
+
+    insert_history_local(&mut statements, &transaction, history, value).await?;
+
+    statements.run("commit", transaction.commit()).await?;
+    Ok(())
+}
+
+/// Executes the whole transaction with a single call of the `tpcb_like`
+/// stored procedure.
+pub async fn standalone_server(
+    job_config: Pin<Arc<JobConfig>>,
+    value: Transaction,
+) -> Result<(), anyhow::Error> {
+    job_config
+        .statements
+        .transaction(Builtin::TpcbLike.name())
+        .run("call tpcb_like", async {
+            job_config
+                .connections
+                .get_connection()
+                .call(
+                    "tpcb_like",
+                    (
+                        value.aid,
+                        value.tid,
+                        value.bid,
+                        value.delta,
+                        value.uuid,
+                        value.time,
+                    ),
+                )
+                .await
+                .and_then(|response| response.decode_result::<u64>())
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Adds the delta to the account balance through the router.
+pub async fn update_account(
+    statements: &mut Recorder<'_>,
+    transaction: &tarantool_rs::Transaction,
+    config: &Config,
+    value: &Transaction,
+) -> Result<Balance, anyhow::Error> {
+    let bucket_id = config.account_bucket_id(value.aid);
+    statements
+        .run("callrw accounts:update", async {
+            transaction
+                .call(
+                    "vshard.router.callrw",
+                    (
+                        bucket_id,
+                        "box.space.ttbench_accounts:update",
+                        (value.aid, (("+", "abalance", value.delta),)),
+                    ),
+                )
+                .await
+                .and_then(|response| response.decode_result::<Balance>())
+        })
         .await
-        .and_then(|response| response.decode_result::<Account>())?;
-
-    transaction
-        .call(
-            "vshard.router.callbro",
-            (
-                aid_bucket_id,
-                "box.space.ttbench_accounts:get",
-                (value.aid,),
-            ),
-        )
+}
+
+/// Reads the account balance through the router.
+pub async fn get_account(
+    statements: &mut Recorder<'_>,
+    transaction: &tarantool_rs::Transaction,
+    config: &Config,
+    value: &Transaction,
+) -> Result<Balance, anyhow::Error> {
+    let bucket_id = config.account_bucket_id(value.aid);
+    statements
+        .run("callbro accounts:get", async {
+            transaction
+                .call(
+                    "vshard.router.callbro",
+                    (bucket_id, "box.space.ttbench_accounts:get", (value.aid,)),
+                )
+                .await
+                .and_then(|response| response.decode_result::<Balance>())
+        })
         .await
-        .and_then(|response| response.decode_result::<Account>())?;
-
-    let tid_bucket_id = calculate_bucket_id(value.tid.to_string(), job_config.config.bucket_count);
-    transaction
-        .call(
-            "vshard.router.callrw",
-            (
-                tid_bucket_id,
-                "box.space.ttbench_tellers:update",
-                (value.tid, (("+", "tbalance", value.delta),)),
-            ),
-        )
+}
+
+/// Inserts the history row through the router, it is sharded by its uuid.
+pub async fn insert_history(
+    statements: &mut Recorder<'_>,
+    transaction: &tarantool_rs::Transaction,
+    config: &Config,
+    value: Transaction,
+) -> Result<Transaction, anyhow::Error> {
+    let bucket_id = calculate_bucket_id(&value.uuid, config.bucket_count);
+    statements
+        .run("callrw history:insert", async {
+            transaction
+                .call(
+                    "vshard.router.callrw",
+                    (
+                        bucket_id,
+                        "box.space.ttbench_history:insert",
+                        (Transaction { bucket_id, ..value },),
+                    ),
+                )
+                .await
+                .and_then(|response| response.decode_result::<Transaction>())
+        })
         .await
-        .and_then(|response| response.decode_result::<Teller>())?;
-
-    let bid_bucket_id = calculate_bucket_id(value.bid.to_string(), job_config.config.bucket_count);
-    transaction
-        .call(
-            "vshard.router.callrw",
-            (
-                bid_bucket_id,
-                "box.space.ttbench_branches:update",
-                (value.bid, (("+", "bbalance", value.delta),)),
-            ),
+}
+
+/// Adds the delta to the account balance in the accounts space.
+pub async fn update_account_local(
+    statements: &mut Recorder<'_>,
+    transaction: &tarantool_rs::Transaction,
+    accounts: u32,
+    value: &Transaction,
+) -> Result<(u64, i64), anyhow::Error> {
+    statements
+        .run("update accounts", async {
+            transaction
+                .update(accounts, 0, (value.aid,), (("+", "abalance", value.delta),))
+                .await?
+                .decode::<(u64, i64)>()
+                .map_err(tarantool_rs::Error::from)
+        })
+        .await
+}
+
+/// Reads the account balance from the accounts space.
+pub async fn select_account_local(
+    statements: &mut Recorder<'_>,
+    transaction: &tarantool_rs::Transaction,
+    accounts: u32,
+    value: &Transaction,
+) -> Result<Vec<(u64, i64)>, anyhow::Error> {
+    statements
+        .run(
+            "select accounts",
+            transaction.select::<(u64, i64), _>(accounts, 0, None, None, None, (value.aid,)),
         )
         .await
-        .and_then(|response| response.decode_result::<Branch>())?;
-
-    let bucket_id = calculate_bucket_id(&value.uuid, job_config.config.bucket_count);
-    transaction
-        .call(
-            "vshard.router.callrw",
-            (
-                bucket_id,
-                "box.space.ttbench_history:insert",
-                (Transaction { bucket_id, ..value },),
+}
+
+/// Inserts the history row into the history space.
+pub async fn insert_history_local(
+    statements: &mut Recorder<'_>,
+    transaction: &tarantool_rs::Transaction,
+    history: u32,
+    value: Transaction,
+) -> Result<DmoResponse, anyhow::Error> {
+    statements
+        .run(
+            "insert history",
+            transaction.insert(
+                history,
+                (
+                    value.uuid,
+                    value.tid,
+                    value.bid,
+                    value.aid,
+                    value.delta,
+                    value.time,
+                ),
             ),
         )
         .await
-        .and_then(|response| response.decode_result::<Transaction>())?;
-
-    transaction.commit().await?;
-    Ok(())
 }
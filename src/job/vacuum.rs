@@ -0,0 +1,111 @@
+use std::{pin::Pin, sync::Arc};
+
+use tarantool_rs::ExecutorExt;
+
+use crate::{config::Topology, job::JobConfig};
+
+use super::schema::{ACCOUNTS, BRANCHES, HISTORY, TELLERS};
+
+/// Compacts vinyl indexes, waits until the compaction is done and makes a
+/// snapshot, so the benchmark starts with compacted LSM trees and without a
+/// long xlog tail. `index:compact()` only schedules the compaction, which
+/// fails when a compaction task fails or it isn't done within the timeout.
+const VACUUM: &str = r#"
+local fiber = require("fiber")
+local names, timeout = ...
+local failed = box.stat.vinyl().scheduler.tasks_failed
+local compacted = false
+for _, name in ipairs(names) do
+    local space = box.space[name]
+    if space ~= nil and space.engine == "vinyl" then
+        for id, index in pairs(space.index) do
+            if type(id) == "number" then
+                index:compact()
+                compacted = true
+            end
+        end
+    end
+end
+if compacted then
+    local deadline = fiber.clock() + timeout
+    while true do
+        local scheduler = box.stat.vinyl().scheduler
+        if scheduler.tasks_failed > failed then
+            error("vinyl compaction failed, see the instance log")
+        end
+        if scheduler.compaction_queue == 0 and scheduler.tasks_inprogress == 0 then
+            break
+        end
+        if fiber.clock() > deadline then
+            error(string.format("vinyl compaction is not done in %d s", timeout))
+        end
+        fiber.sleep(0.1)
+    end
+end
+box.snapshot()
+"#;
+
+pub async fn run(job_config: Pin<Arc<JobConfig>>, id: u64) -> Result<(), anyhow::Error> {
+    match job_config.config.topology {
+        Topology::Vshard => vshard(job_config, id).await,
+        Topology::Box => standalone(job_config, id).await,
+    }
+}
+
+pub async fn vshard(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    job_config
+        .connections
+        .get_maintenance_connection()
+        .eval(
+            r#"
+local vacuum, names, timeout = ...
+local nb = require("net.box")
+local shards, err = vshard.router.routeall()
+if err then
+    return nil, err
+end
+local futures = {}
+for _, shard in pairs(shards) do
+    local conn, err = nb.connect(shard.master.uri)
+    if err then
+        return nil, err
+    end
+    table.insert(futures, {conn, conn:eval(vacuum, {names, timeout}, {is_async = true})})
+end
+for _, future in ipairs(futures) do
+    local conn, future = unpack(future)
+    local _, err = future:wait_result()
+    conn:close()
+    if err then
+        return nil, tostring(err)
+    end
+end
+return true
+            "#,
+            (
+                VACUUM,
+                [ACCOUNTS, TELLERS, BRANCHES, HISTORY],
+                job_config.config.vacuum_timeout.as_secs(),
+            ),
+        )
+        .await
+        .and_then(|response| response.decode_result::<bool>())?;
+
+    Ok(())
+}
+
+pub async fn standalone(job_config: Pin<Arc<JobConfig>>, _id: u64) -> Result<(), anyhow::Error> {
+    job_config
+        .connections
+        .get_maintenance_connection()
+        .eval(
+            VACUUM,
+            (
+                [ACCOUNTS, TELLERS, BRANCHES, HISTORY],
+                job_config.config.vacuum_timeout.as_secs(),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,330 @@
+use std::{
+    fmt::Display,
+    ops::SubAssign,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use rand::Rng;
+
+use crate::{
+    config::{Config, Topology, TpcbMode},
+    script::Script,
+    status::{iterations::Iterations, time::Time, Target},
+};
+
+use super::{
+    script::ScriptRun,
+    tpcb::{Transaction, TransactionGenerator},
+    JobConfig, TaskInfo,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    TpcbLike,
+    SimpleUpdate,
+    SelectOnly,
+}
+
+impl Builtin {
+    const ALL: [Builtin; 3] = [
+        Builtin::TpcbLike,
+        Builtin::SimpleUpdate,
+        Builtin::SelectOnly,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::TpcbLike => "tpcb-like",
+            Builtin::SimpleUpdate => "simple-update",
+            Builtin::SelectOnly => "select-only",
+        }
+    }
+
+    /// Transactions through `vshard.router` are committed statement by
+    /// statement on the storages, so only select-only and the server-side
+    /// tpcb-like mode are atomic there.
+    pub fn is_atomic(&self, config: &Config) -> bool {
+        match (self, config.topology) {
+            (_, Topology::Box) | (Builtin::SelectOnly, Topology::Vshard) => true,
+            (Builtin::TpcbLike, Topology::Vshard) => config.tpcb_mode == TpcbMode::Server,
+            (Builtin::SimpleUpdate, Topology::Vshard) => false,
+        }
+    }
+
+    /// Whether every transaction inserts a history row.
+    pub fn writes_history(&self) -> bool {
+        *self != Builtin::SelectOnly
+    }
+}
+
+impl FromStr for Builtin {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Builtin::ALL
+            .into_iter()
+            .find(|builtin| builtin.name() == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown builtin script '{name}', available: {}",
+                    Builtin::ALL.map(|builtin| builtin.name()).join(", ")
+                )
+            })
+    }
+}
+
+impl Display for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Builtin::TpcbLike => write!(f, "<builtin: TPC-B (sort of)>"),
+            Builtin::SimpleUpdate => write!(f, "<builtin: simple update>"),
+            Builtin::SelectOnly => write!(f, "<builtin: select only>"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WorkloadKind {
+    Builtin(Builtin),
+    Script(Arc<Script>),
+}
+
+/// Transaction body picked for each transaction with probability
+/// proportional to its weight.
+#[derive(Debug, Clone)]
+pub struct Workload {
+    pub kind: WorkloadKind,
+    pub weight: u64,
+}
+
+impl Display for Workload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            WorkloadKind::Builtin(builtin) => write!(f, "{builtin}"),
+            WorkloadKind::Script(script) => write!(f, "{}", script.name),
+        }
+    }
+}
+
+/// Transaction of the workload with the given index in the config.
+#[derive(Clone, Debug)]
+pub struct Task {
+    pub script: usize,
+    pub kind: TaskKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum TaskKind {
+    Builtin(Builtin, Transaction),
+    Script(ScriptRun),
+}
+
+impl TaskInfo for Task {
+    fn script_id(&self) -> usize {
+        self.script
+    }
+
+    /// Scripts through `vshard.router` are atomic only when they are a
+    /// single call.
+    fn is_atomic(&self, config: &Config) -> bool {
+        match (&self.kind, config.topology) {
+            (TaskKind::Builtin(builtin, _), _) => builtin.is_atomic(config),
+            (TaskKind::Script(run), Topology::Box) => {
+                run.script.transaction || run.script.statements.len() == 1
+            }
+            (TaskKind::Script(run), Topology::Vshard) => run.script.statements.len() == 1,
+        }
+    }
+
+    /// History rows written by scripts are unknown.
+    fn writes_history(&self) -> bool {
+        match &self.kind {
+            TaskKind::Builtin(builtin, _) => builtin.writes_history(),
+            TaskKind::Script(_) => false,
+        }
+    }
+}
+
+pub struct WorkloadGenerator {
+    workloads: Vec<Workload>,
+    total_weight: u64,
+    transactions: TransactionGenerator,
+}
+
+impl WorkloadGenerator {
+    pub fn new(config: &Config) -> Self {
+        WorkloadGenerator {
+            workloads: config.workloads.clone(),
+            total_weight: config
+                .workloads
+                .iter()
+                .map(|workload| workload.weight)
+                .sum(),
+            transactions: TransactionGenerator::new(config),
+        }
+    }
+
+    pub fn generate(&self) -> Task {
+        let mut point = rand::thread_rng().gen_range(0..self.total_weight);
+        let (script, workload) = self
+            .workloads
+            .iter()
+            .enumerate()
+            .find(|(_, workload)| {
+                if point < workload.weight {
+                    return true;
+                }
+                point -= workload.weight;
+                false
+            })
+            .expect("point is less than total weight");
+        let kind = match &workload.kind {
+            WorkloadKind::Builtin(builtin) => {
+                TaskKind::Builtin(*builtin, self.transactions.generate())
+            }
+            WorkloadKind::Script(script) => TaskKind::Script(ScriptRun {
+                script: script.clone(),
+                values: script.generate(),
+            }),
+        };
+        Task { script, kind }
+    }
+}
+
+impl Target for Iterations<WorkloadGenerator> {
+    type Output = Task;
+
+    fn new(config: &Config) -> Self {
+        Iterations {
+            generator: WorkloadGenerator::new(config),
+            counter: Arc::new(Mutex::new(config.transactions_total)),
+        }
+    }
+
+    fn make_progress(&self) -> Result<Option<Self::Output>, anyhow::Error> {
+        let mut locked = self.counter.lock().unwrap();
+        if locked.eq(&0) {
+            return Ok(None);
+        }
+        locked.sub_assign(1);
+        Ok(Some(self.generator.generate()))
+    }
+
+    fn is_reached(&self) -> Result<bool, anyhow::Error> {
+        let locked = self.counter.lock().unwrap();
+        if locked.eq(&0) {
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+impl Target for Time<WorkloadGenerator> {
+    type Output = Task;
+
+    fn new(config: &Config) -> Self {
+        Time::new(WorkloadGenerator::new(config), config.time)
+    }
+
+    fn make_progress(&self) -> Result<Option<Self::Output>, anyhow::Error> {
+        if self.is_elapsed() {
+            return Ok(None);
+        }
+        Ok(Some(self.generator.generate()))
+    }
+
+    fn is_reached(&self) -> Result<bool, anyhow::Error> {
+        Ok(self.is_elapsed())
+    }
+
+    fn remaining(&self) -> Option<Duration> {
+        Some(Time::remaining(self))
+    }
+}
+
+pub async fn run(job_config: Pin<Arc<JobConfig>>, task: Task) -> Result<(), anyhow::Error> {
+    let topology = job_config.config.topology;
+    match task.kind {
+        TaskKind::Builtin(Builtin::TpcbLike, transaction) => {
+            match (topology, job_config.config.tpcb_mode) {
+                (Topology::Vshard, TpcbMode::Client) => {
+                    super::tpcb::vshard(job_config, transaction).await
+                }
+                (Topology::Vshard, TpcbMode::Server) => {
+                    super::tpcb::vshard_server(job_config, transaction).await
+                }
+                (Topology::Box, TpcbMode::Client) => {
+                    super::tpcb::standalone(job_config, transaction).await
+                }
+                (Topology::Box, TpcbMode::Server) => {
+                    super::tpcb::standalone_server(job_config, transaction).await
+                }
+            }
+        }
+        TaskKind::Builtin(Builtin::SimpleUpdate, transaction) => match topology {
+            Topology::Vshard => super::simple_update::vshard(job_config, transaction).await,
+            Topology::Box => super::simple_update::standalone(job_config, transaction).await,
+        },
+        TaskKind::Builtin(Builtin::SelectOnly, transaction) => match topology {
+            Topology::Vshard => super::select_only::vshard(job_config, transaction).await,
+            Topology::Box => super::select_only::standalone(job_config, transaction).await,
+        },
+        TaskKind::Script(run) => super::script::run(job_config, run).await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{
+        args,
+        config::{Config, TpcbMode},
+        job::{tpcb::TransactionGenerator, TaskInfo},
+        status::{time::Time, Target},
+    };
+
+    use super::{Builtin, Task, TaskKind, WorkloadGenerator};
+
+    #[test]
+    fn test_time_target() {
+        let mut config = Config::new(&args::init(["ttbench", "run", "-T", "1"])).unwrap();
+
+        config.time = Duration::ZERO;
+        let time = <Time<WorkloadGenerator> as Target>::new(&config);
+        assert!(time.is_reached().unwrap());
+        assert!(time.make_progress().unwrap().is_none());
+        assert_eq!(Target::remaining(&time), Some(Duration::ZERO));
+
+        config.time = Duration::from_secs(3600);
+        let time = <Time<WorkloadGenerator> as Target>::new(&config);
+        assert!(!time.is_reached().unwrap());
+        assert!(time.make_progress().unwrap().is_some());
+        assert!(Target::remaining(&time).unwrap() > Duration::from_secs(3500));
+    }
+
+    #[test]
+    fn test_is_atomic() {
+        let mut config = Config::new(&args::init(["ttbench", "run"])).unwrap();
+        let transaction = TransactionGenerator::new(&config).generate();
+        let task = |builtin| Task {
+            script: 0,
+            kind: TaskKind::Builtin(builtin, transaction.clone()),
+        };
+
+        assert!(!task(Builtin::TpcbLike).is_atomic(&config));
+        assert!(!task(Builtin::SimpleUpdate).is_atomic(&config));
+        assert!(task(Builtin::SelectOnly).is_atomic(&config));
+
+        config.tpcb_mode = TpcbMode::Server;
+        assert!(task(Builtin::TpcbLike).is_atomic(&config));
+
+        let config = Config::new(&args::init(["ttbench", "run", "--topology", "box"])).unwrap();
+        assert!(task(Builtin::TpcbLike).is_atomic(&config));
+        assert!(task(Builtin::SimpleUpdate).is_atomic(&config));
+    }
+}
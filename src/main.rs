@@ -1,17 +1,37 @@
-use std::sync::{atomic::AtomicU64, Arc, Mutex};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{atomic::AtomicU64, Arc, Mutex},
+    time::Instant,
+};
 
-use job::tpcb::TransactionGenerator;
-use status::{iterations::Iterations, Progress};
+use anyhow::anyhow;
+use args::Command;
+use config::{BenchMod, InitStep, OutputFormat, Topology, TpcbMode};
+use job::{
+    check::{CheckStatus, CheckSummary},
+    generate::{Routes, Table},
+    schema::{ACCOUNTS, BRANCHES, HISTORY, TELLERS},
+    workload::{Task, WorkloadGenerator},
+    JobConfig, Spawn,
+};
+use report::{InitSummary, Report, StepTiming};
+use status::{iterations::Iterations, statements::Statements, time::Time, Summary, Target};
+use tokio::task::JoinHandle;
+use utils::message;
 
 mod args;
 mod config;
 mod job;
+mod report;
+mod script;
 mod status;
 mod utils;
 
 #[cfg(not(tarpaulin_include))]
 pub fn main() -> Result<(), anyhow::Error> {
     let cli = args::init(std::env::args());
+    utils::set_messages_to_stderr(cli.output != OutputFormat::Text);
     let config = config::Config::new(&cli)?;
     let runtime = tokio::runtime::Builder::new_multi_thread()
         // TODO Продумать количество тредов
@@ -19,118 +39,241 @@ pub fn main() -> Result<(), anyhow::Error> {
         .enable_all()
         .build()?;
 
-    runtime.block_on(run_bench(config))?;
+    runtime.block_on(run(cli, config))?;
 
     Ok(())
 }
 
-async fn run_bench(config: config::Config) -> Result<(), anyhow::Error> {
+async fn run(cli: args::Cli, config: config::Config) -> Result<(), anyhow::Error> {
     let connections = job::connections::Pool::new(&config.instances).await?;
-    let job_config = Arc::pin(job::JobConfig {
+    if config.topology == Topology::Vshard {
+        connections.expose_vshard().await?;
+    }
+    let job_config = Arc::pin(JobConfig {
         config: config.clone(),
         connections,
+        spaces: Default::default(),
+        statements: Statements::new(&config),
     });
 
-    if job_config.config.contains_step(&config::InitStep::Create) {
-        let status = Arc::pin(status::Status::single_run());
-        tokio::spawn(
-            job::Job::new(0, job::create::vshard, job_config.clone(), status.clone()).await?,
-        );
-        status.wait_the_end().await?;
+    let mut report = Report::new(&config);
+    match cli.command {
+        Command::Init => report.init = Some(init(&config, job_config).await?),
+        Command::Run => {
+            if cli.init {
+                report.init = Some(init(&config, job_config.clone()).await?);
+            } else if config.tpcb_mode == TpcbMode::Server {
+                single_run(job::create::procedures, job_config.clone()).await?;
+            }
+            let (summary, check) = match config.mode {
+                BenchMod::Iterations => {
+                    bench::<Iterations<WorkloadGenerator>>(&config, job_config.clone()).await?
+                }
+                BenchMod::Time => {
+                    bench::<Time<WorkloadGenerator>>(&config, job_config.clone()).await?
+                }
+            };
+            report.result = Some(summary);
+            report.check = check;
+            report.statements = job_config.statements.summary();
+        }
+        Command::Cleanup => return single_run(job::drop::run, job_config).await,
+        Command::Check => return single_run(job::check::run, job_config).await,
     }
+    report.print(config.output)?;
+    // The report of a failed check is printed too, so the run can be kept.
+    match report.check {
+        Some(check) if check.status == CheckStatus::Failed => Err(anyhow!(
+            "consistency check failed: {}",
+            check.problems.join(", ")
+        )),
+        _ => Ok(()),
+    }
+}
 
-    if job_config
-        .config
-        .contains_step(&config::InitStep::GenerateData)
-    {
-        let status = Arc::pin(status::Status {
-            target: status::iterations::Iterations::new(
-                AtomicU64::new(0),
-                Arc::new(Mutex::new(config.test_config.ttbench_accounts)),
-            ),
-            progress: Progress::default(),
-            logger: status::Logger {},
-        });
-        for id in 0..config.jobs {
-            tokio::spawn(
-                job::Job::new(
-                    id,
-                    job::accounts::vshard,
-                    job_config.clone(),
-                    status.clone(),
-                )
-                .await?,
-            );
+/// Executes the init steps in the given order, returns their timings.
+async fn init(
+    config: &config::Config,
+    job_config: Pin<Arc<JobConfig>>,
+) -> Result<InitSummary, anyhow::Error> {
+    if config.init_steps.need_spaces() {
+        let names = [ACCOUNTS, TELLERS, BRANCHES, HISTORY];
+        let counts = job::check::counts(&job_config, &names).await?;
+        if let Some((name, _)) = names.iter().zip(counts).find(|(_, count)| *count < 0) {
+            return Err(anyhow!(
+                "space '{name}' doesn't exist, add 't' to the init steps"
+            ));
         }
-        status.wait_the_end().await?;
-
-        let jobs = if config.jobs > config.test_config.ttbench_tellers {
-            config.test_config.ttbench_tellers
-        } else {
-            config.jobs
-        };
-        let status = Arc::pin(status::Status {
-            target: status::iterations::Iterations::new(
-                AtomicU64::new(0),
-                Arc::new(Mutex::new(config.test_config.ttbench_tellers)),
-            ),
-            progress: Progress::default(),
-            logger: status::Logger {},
-        });
-        for id in 0..jobs {
-            tokio::spawn(
-                job::Job::new(id, job::tellers::vshard, job_config.clone(), status.clone()).await?,
-            );
+    }
+
+    let started = Instant::now();
+    let mut steps = Vec::new();
+    for step in config.init_steps.iter() {
+        message!("{step}...");
+        let step_started = Instant::now();
+        match step {
+            InitStep::Drop => single_run(job::drop::run, job_config.clone()).await?,
+            InitStep::Create => single_run(job::create::run, job_config.clone()).await?,
+            InitStep::Primary => single_run(job::index::primary, job_config.clone()).await?,
+            InitStep::Foreign => single_run(job::index::foreign, job_config.clone()).await?,
+            InitStep::GenerateData => {
+                single_run(job::create::procedures, job_config.clone()).await?;
+                let routes = Arc::new(match config.topology {
+                    Topology::Vshard => Routes::fetch(&job_config).await?,
+                    Topology::Box => Routes::default(),
+                });
+                for table in Table::ALL {
+                    let routes = routes.clone();
+                    generate(
+                        config,
+                        job_config.clone(),
+                        table.batches(config),
+                        move |job_config, batch| {
+                            job::generate::client(job_config, table, routes.clone(), batch)
+                        },
+                    )
+                    .await
+                    .map_err(|err| anyhow!("{}: {err}", table.space()))?;
+                }
+            }
+            InitStep::GenerateDataServer => {
+                for table in Table::ALL {
+                    generate(
+                        config,
+                        job_config.clone(),
+                        table.chunks(config),
+                        move |job_config, chunk| job::generate::server(job_config, table, chunk),
+                    )
+                    .await
+                    .map_err(|err| anyhow!("{}: {err}", table.space()))?;
+                }
+            }
+            InitStep::Vacuum => single_run(job::vacuum::run, job_config.clone()).await?,
         }
-        status.wait_the_end().await?;
-
-        let jobs = if config.jobs > config.test_config.ttbench_branches {
-            config.test_config.ttbench_branches
-        } else {
-            config.jobs
-        };
-        let status = Arc::pin(status::Status {
-            target: status::iterations::Iterations::new(
-                AtomicU64::new(0),
-                Arc::new(Mutex::new(config.test_config.ttbench_branches)),
-            ),
-            progress: Progress::default(),
-            logger: status::Logger {},
+        steps.push(StepTiming {
+            step: step.to_string(),
+            duration_s: step_started.elapsed().as_secs_f64(),
         });
-        for id in 0..jobs {
-            tokio::spawn(
-                job::Job::new(
-                    id,
-                    job::branches::vshard,
-                    job_config.clone(),
-                    status.clone(),
-                )
-                .await?,
-            );
-        }
-        status.wait_the_end().await?;
     }
+    let summary = InitSummary {
+        duration_s: started.elapsed().as_secs_f64(),
+        steps,
+    };
+    message!(
+        "done in {:.2} s ({})",
+        summary.duration_s,
+        summary
+            .steps
+            .iter()
+            .map(|step| format!("{} {:.2} s", step.step, step.duration_s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
 
-    let status = Arc::pin(status::Status::<Iterations<TransactionGenerator>>::try_from(&config)?);
-    for id in 0..config.jobs {
-        tokio::spawn(
-            job::Job::new(id, job::tpcb::vshard, job_config.clone(), status.clone()).await?,
-        );
+    Ok(summary)
+}
+
+/// Runs the data generation for every chunk of a table.
+async fn generate<S, F>(
+    config: &config::Config,
+    job_config: Pin<Arc<JobConfig>>,
+    chunks: u64,
+    spawner: S,
+) -> Result<(), anyhow::Error>
+where
+    S: Spawn<u64, Output = F> + Unpin + Clone + Send + 'static,
+    F: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+{
+    let status = Arc::pin(status::Status {
+        logger: status::Logger::new(config),
+        ..status::Status::new(status::iterations::Iterations::new(
+            AtomicU64::new(0),
+            Arc::new(Mutex::new(chunks)),
+        ))
+    });
+    let mut jobs = Vec::new();
+    for id in 0..config.jobs.min(chunks) {
+        jobs.push(tokio::spawn(
+            job::Job::new(id, spawner.clone(), job_config.clone(), status.clone()).await?,
+        ));
     }
     status.wait_the_end().await?;
-    println!("{}", status.progress);
-
-    if job_config.config.contains_step(&config::InitStep::Drop) {
-        let status = Arc::pin(status::Status {
-            target: status::iterations::Iterations::new(AtomicU64::new(0), Arc::new(Mutex::new(1))),
-            progress: Progress::default(),
-            logger: status::Logger {},
-        });
-        tokio::spawn(
-            job::Job::new(0, job::drop::vshard, job_config.clone(), status.clone()).await?,
-        );
-        status.wait_the_end().await?;
+    join(jobs).await?;
+    match status.progress.failures() {
+        0 => Ok(()),
+        failures => Err(anyhow!("generation failed for {failures} chunks")),
     }
+}
 
+/// Waits for the spawned jobs, returning the first error a job stopped with.
+/// Failed transactions are counted by the jobs and are not errors.
+async fn join(jobs: Vec<JoinHandle<Result<(), anyhow::Error>>>) -> Result<(), anyhow::Error> {
+    for job in jobs {
+        job.await??;
+    }
     Ok(())
 }
+
+/// Runs a job once, failing if it fails.
+async fn single_run<S, F>(spawner: S, job_config: Pin<Arc<JobConfig>>) -> Result<(), anyhow::Error>
+where
+    S: Spawn<u64, Output = F> + Unpin + Clone + Send + 'static,
+    F: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+{
+    let status = Arc::pin(status::Status::single_run());
+    let job = tokio::spawn(job::Job::new(0, spawner, job_config, status.clone()).await?);
+    status.wait_the_end().await?;
+    join(vec![job]).await?;
+    match status.progress.failures() {
+        0 => Ok(()),
+        _ => Err(anyhow!("job failed")),
+    }
+}
+
+async fn bench<T>(
+    config: &config::Config,
+    job_config: Pin<Arc<JobConfig>>,
+) -> Result<(Summary, Option<CheckSummary>), anyhow::Error>
+where
+    T: Target<Output = Task> + Send + Sync + 'static,
+{
+    let history = match config.check {
+        true => Some(job::check::snapshot(&job_config).await?),
+        false => None,
+    };
+    let status = Arc::pin(status::Status::<T>::try_from(config)?);
+    let mut jobs = Vec::new();
+    for id in 0..config.jobs {
+        jobs.push(tokio::spawn(
+            job::Job::new(id, job::workload::run, job_config.clone(), status.clone()).await?,
+        ));
+    }
+    status.wait_the_end().await?;
+    join(jobs).await?;
+    if config.output == OutputFormat::Text {
+        println!("{}", status.progress);
+        if config.failures_detailed {
+            for (code, count) in status.progress.failures_detailed() {
+                println!("failures with {code}: {count}");
+            }
+        }
+        if job_config.statements.is_enabled() {
+            println!("{}", job_config.statements);
+        }
+    }
+
+    let check = match history {
+        Some(before) => {
+            let run = job::check::RunHistory {
+                before,
+                progress: &status.progress,
+            };
+            let check = job::check::consistency(&job_config, Some(run)).await?;
+            message!("consistency check: {}", check.status.name());
+            Some(check)
+        }
+        None => None,
+    };
+
+    Ok((status.progress.summary(), check))
+}
@@ -0,0 +1,306 @@
+use serde::Serialize;
+
+use crate::{
+    config::{BenchMod, Config, OutputFormat},
+    job::{check::CheckSummary, error::ErrorClass},
+    status::{statements::WorkloadSummary, Summary},
+};
+
+/// Result document of `--output json` and `--output csv`.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub config: ConfigSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub init: Option<InitSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Summary>,
+    /// with `--check`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check: Option<CheckSummary>,
+    /// with `--report-per-statement`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub statements: Vec<WorkloadSummary>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConfigSummary {
+    pub scale: u64,
+    pub topology: String,
+    pub tpcb_mode: String,
+    pub engine: String,
+    pub primary_index: String,
+    pub id_type: String,
+    pub fillfactor: u64,
+    pub jobs: u64,
+    pub connections: usize,
+    pub workloads: Vec<WorkloadWeight>,
+    pub mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_s: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transactions_per_job: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_limit_ms: Option<u128>,
+    pub max_tries: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WorkloadWeight {
+    pub name: String,
+    pub weight: u64,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct InitSummary {
+    pub duration_s: f64,
+    pub steps: Vec<StepTiming>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StepTiming {
+    pub step: String,
+    pub duration_s: f64,
+}
+
+impl ConfigSummary {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            scale: config.test_config.ttbench_branches,
+            topology: config.topology.to_string(),
+            tpcb_mode: config.tpcb_mode.to_string(),
+            engine: config.engine.name().into(),
+            primary_index: config.primary_index.name().into(),
+            id_type: config.id_type.name().into(),
+            fillfactor: config.fillfactor,
+            jobs: config.jobs,
+            connections: config
+                .instances
+                .iter()
+                .map(|instance| instance.connections)
+                .sum(),
+            workloads: config
+                .workloads
+                .iter()
+                .map(|workload| WorkloadWeight {
+                    name: workload.to_string(),
+                    weight: workload.weight,
+                })
+                .collect(),
+            mode: config.mode.to_string(),
+            duration_s: matches!(config.mode, BenchMod::Time).then(|| config.time.as_secs()),
+            transactions_per_job: matches!(config.mode, BenchMod::Iterations)
+                .then_some(config.transactions),
+            rate: config.rate,
+            latency_limit_ms: config.latency_limit.map(|limit| limit.as_millis()),
+            max_tries: config.max_tries,
+        }
+    }
+}
+
+impl Report {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            config: ConfigSummary::new(config),
+            init: None,
+            result: None,
+            check: None,
+            statements: Vec::new(),
+        }
+    }
+
+    /// Prints the document to stdout in the given format, the text report
+    /// is printed while running.
+    pub fn print(&self, format: OutputFormat) -> Result<(), anyhow::Error> {
+        match format {
+            OutputFormat::Text => {}
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+            OutputFormat::Csv => {
+                let (names, values): (Vec<_>, Vec<_>) = self.csv_fields()?.into_iter().unzip();
+                println!("{}", names.join(","));
+                println!(
+                    "{}",
+                    values
+                        .iter()
+                        .map(|value| csv_field(value))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Fields of the CSV line. The columns are the same for every run, so
+    /// lists and maps with keys depending on the run are serialized to JSON
+    /// in a single field, missing values are empty and per-statement results
+    /// are left out.
+    fn csv_fields(&self) -> Result<Vec<(String, String)>, anyhow::Error> {
+        fn optional<T: ToString>(value: Option<T>) -> String {
+            value.map(|value| value.to_string()).unwrap_or_default()
+        }
+
+        let config = &self.config;
+        let mut fields = vec![
+            ("config.scale", config.scale.to_string()),
+            ("config.topology", config.topology.clone()),
+            ("config.tpcb_mode", config.tpcb_mode.clone()),
+            ("config.engine", config.engine.clone()),
+            ("config.primary_index", config.primary_index.clone()),
+            ("config.id_type", config.id_type.clone()),
+            ("config.fillfactor", config.fillfactor.to_string()),
+            ("config.jobs", config.jobs.to_string()),
+            ("config.connections", config.connections.to_string()),
+            (
+                "config.workloads",
+                serde_json::to_string(&config.workloads)?,
+            ),
+            ("config.mode", config.mode.clone()),
+            ("config.duration_s", optional(config.duration_s)),
+            (
+                "config.transactions_per_job",
+                optional(config.transactions_per_job),
+            ),
+            ("config.rate", optional(config.rate)),
+            ("config.latency_limit_ms", optional(config.latency_limit_ms)),
+            ("config.max_tries", config.max_tries.to_string()),
+            (
+                "init.duration_s",
+                optional(self.init.as_ref().map(|init| init.duration_s)),
+            ),
+            (
+                "init.steps",
+                match &self.init {
+                    Some(init) => serde_json::to_string(&init.steps)?,
+                    None => String::new(),
+                },
+            ),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect::<Vec<_>>();
+
+        let result = self.result.as_ref();
+        let value = |get: fn(&Summary) -> String| result.map(get).unwrap_or_default();
+        let result_fields: [(&str, SummaryField); 18] = [
+            ("duration_s", |result| result.duration_s.to_string()),
+            ("processed", |result| result.processed.to_string()),
+            ("failed", |result| result.failed.to_string()),
+            ("skipped", |result| result.skipped.to_string()),
+            ("retried", |result| result.retried.to_string()),
+            ("retries", |result| result.retries.to_string()),
+            ("tps", |result| result.tps.to_string()),
+            ("latency_ms.average", |result| {
+                result.latency_ms.average.to_string()
+            }),
+            ("latency_ms.stddev", |result| {
+                result.latency_ms.stddev.to_string()
+            }),
+            ("latency_ms.min", |result| result.latency_ms.min.to_string()),
+            ("latency_ms.p50", |result| result.latency_ms.p50.to_string()),
+            ("latency_ms.p90", |result| result.latency_ms.p90.to_string()),
+            ("latency_ms.p95", |result| result.latency_ms.p95.to_string()),
+            ("latency_ms.p99", |result| result.latency_ms.p99.to_string()),
+            ("latency_ms.p99.9", |result| {
+                result.latency_ms.p99_9.to_string()
+            }),
+            ("latency_ms.max", |result| result.latency_ms.max.to_string()),
+            ("lag_ms.average", |result| {
+                optional(result.lag_ms.as_ref().map(|lag| lag.average))
+            }),
+            ("lag_ms.max", |result| {
+                optional(result.lag_ms.as_ref().map(|lag| lag.max))
+            }),
+        ];
+        for (name, get) in result_fields {
+            fields.push((format!("result.{name}"), value(get)));
+        }
+        for class in ErrorClass::ALL {
+            let key = class.to_string();
+            let column = key.replace(' ', "_");
+            fields.push((
+                format!("result.failures_by_class.{column}"),
+                optional(result.map(|result| result.failures_by_class[&key])),
+            ));
+            fields.push((
+                format!("result.retries_by_class.{column}"),
+                optional(result.map(|result| result.retries_by_class[&key])),
+            ));
+        }
+        fields.push((
+            "result.failures_by_code".into(),
+            match result {
+                Some(result) => serde_json::to_string(&result.failures_by_code)?,
+                None => String::new(),
+            },
+        ));
+        fields.push((
+            "check.status".into(),
+            optional(self.check.as_ref().map(|check| check.status.name())),
+        ));
+        fields.push((
+            "check.problems".into(),
+            match &self.check {
+                Some(check) => serde_json::to_string(&check.problems)?,
+                None => String::new(),
+            },
+        ));
+        Ok(fields)
+    }
+}
+
+/// Formats a value of the run summary as a CSV field.
+type SummaryField = fn(&Summary) -> String;
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{args, config::Config, job::error::ErrorClass, status::Progress};
+
+    use super::{csv_field, Report};
+
+    #[test]
+    fn test_csv_columns() {
+        let config = Config::new(&args::init(["ttbench", "run", "-T", "10"])).unwrap();
+        let init = Report::new(&config);
+        let mut run = Report::new(&config);
+        let progress = Progress::default();
+        progress.fail(ErrorClass::Conflict, "box error 97".into());
+        progress.fail(ErrorClass::Routing, "vshard WRONG_BUCKET".into());
+        run.result = Some(progress.summary());
+
+        let init = init.csv_fields().unwrap();
+        let run = run.csv_fields().unwrap();
+        let names = |fields: &[(String, String)]| {
+            fields
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&init), names(&run));
+
+        let value = |name: &str| {
+            run.iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.as_str())
+                .unwrap()
+        };
+        assert_eq!(value("config.duration_s"), "10");
+        assert_eq!(value("config.transactions_per_job"), "");
+        assert_eq!(value("result.failed"), "2");
+        assert_eq!(value("result.failures_by_class.transaction_conflict"), "1");
+        assert_eq!(value("result.retries_by_class.bucket_routing"), "0");
+        assert_eq!(
+            csv_field(value("result.failures_by_code")),
+            r#""{""box error 97"":1,""vshard WRONG_BUCKET"":1}""#
+        );
+    }
+}
@@ -0,0 +1,703 @@
+use std::{
+    fmt::Display,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use rand::Rng;
+use rmpv::Value;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{config::TestConfig, utils::calculate_bucket_id};
+
+/// Custom workload loaded with `-f`.
+///
+/// ```toml
+/// # run all statements in one IPROTO transaction
+/// transaction = true
+///
+/// # variables are drawn anew for every transaction, in the given order;
+/// # `scale`, `naccounts`, `ntellers`, `nbranches` and the largest ids
+/// # `max_aid`, `max_tid` and `max_bid` are predefined
+/// [variables]
+/// aid = { type = "uniform", min = 0, max = ":max_aid" }
+/// bucket_id = { type = "bucket_id", of = ":aid" }
+///
+/// [[statements]]
+/// call = "vshard.router.callbro"
+/// args = [":bucket_id", "box.space.ttbench_accounts:get", [":aid"]]
+/// ```
+///
+/// Besides `call` a statement can be `eval` (with `args`), `select` (with
+/// `key`, optional `index` id and `limit`), `insert` and `replace` (with
+/// `tuple`), `update` (with `key` and `operations`) and `execute` (SQL
+/// with `binds`). Strings of the form `":name"` are replaced by variables.
+#[derive(Debug)]
+pub struct Script {
+    pub name: String,
+    pub transaction: bool,
+    variables: Vec<(String, Variable)>,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug)]
+pub enum Statement {
+    Call {
+        function: String,
+        args: Template,
+    },
+    Eval {
+        expr: String,
+        args: Template,
+    },
+    Select {
+        space: String,
+        index: u32,
+        limit: Option<u32>,
+        key: Template,
+    },
+    Insert {
+        space: String,
+        tuple: Template,
+    },
+    Replace {
+        space: String,
+        tuple: Template,
+    },
+    Update {
+        space: String,
+        key: Template,
+        operations: Template,
+    },
+    Execute {
+        query: String,
+        binds: Template,
+    },
+}
+
+/// Short description of the statement for `--report-per-statement`.
+impl Display for Statement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Statement::Call { function, .. } => write!(f, "call {function}"),
+            Statement::Eval { expr, .. } => write!(f, "eval {expr}"),
+            Statement::Select { space, .. } => write!(f, "select {space}"),
+            Statement::Insert { space, .. } => write!(f, "insert {space}"),
+            Statement::Replace { space, .. } => write!(f, "replace {space}"),
+            Statement::Update { space, .. } => write!(f, "update {space}"),
+            Statement::Execute { query, .. } => write!(f, "execute {query}"),
+        }
+    }
+}
+
+/// Statement argument with references to variables resolved to indexes.
+#[derive(Debug)]
+pub enum Template {
+    Value(Value),
+    Variable(usize),
+    Array(Vec<Template>),
+    Map(Vec<(String, Template)>),
+}
+
+#[derive(Debug)]
+enum Variable {
+    Constant(Value),
+    Uniform {
+        min: Bound,
+        max: Bound,
+    },
+    Exponential {
+        min: Bound,
+        max: Bound,
+        parameter: f64,
+    },
+    Gaussian {
+        min: Bound,
+        max: Bound,
+        parameter: f64,
+    },
+    BucketId {
+        of: usize,
+        bucket_count: u32,
+    },
+    Uuid,
+    Now,
+}
+
+#[derive(Debug)]
+enum Bound {
+    Value(i64),
+    Variable(usize),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptFile {
+    #[serde(default)]
+    transaction: bool,
+    #[serde(default)]
+    variables: toml::Table,
+    statements: Vec<StatementFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum VariableFile {
+    Uniform {
+        min: BoundFile,
+        max: BoundFile,
+    },
+    Exponential {
+        min: BoundFile,
+        max: BoundFile,
+        parameter: f64,
+    },
+    Gaussian {
+        min: BoundFile,
+        max: BoundFile,
+        parameter: f64,
+    },
+    BucketId {
+        of: String,
+    },
+    Uuid,
+    Now,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BoundFile {
+    Value(i64),
+    Variable(String),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct StatementFile {
+    call: Option<String>,
+    eval: Option<String>,
+    select: Option<String>,
+    insert: Option<String>,
+    replace: Option<String>,
+    update: Option<String>,
+    execute: Option<String>,
+    args: Option<toml::Value>,
+    key: Option<toml::Value>,
+    tuple: Option<toml::Value>,
+    operations: Option<toml::Value>,
+    binds: Option<toml::Value>,
+    index: Option<u32>,
+    limit: Option<u32>,
+}
+
+impl Script {
+    pub fn load(
+        path: &Path,
+        test_config: &TestConfig,
+        bucket_count: u32,
+    ) -> Result<Self, anyhow::Error> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("failed to read script {}: {err}", path.display()))?;
+        Self::parse(
+            path.display().to_string(),
+            &content,
+            test_config,
+            bucket_count,
+        )
+        .map_err(|err| anyhow!("failed to load script {}: {err}", path.display()))
+    }
+
+    pub fn parse(
+        name: String,
+        content: &str,
+        test_config: &TestConfig,
+        bucket_count: u32,
+    ) -> Result<Self, anyhow::Error> {
+        let file: ScriptFile = toml::from_str(content)?;
+
+        let mut variables = vec![
+            // one branch per scale unit
+            ("scale".to_string(), test_config.ttbench_branches),
+            ("naccounts".to_string(), test_config.ttbench_accounts),
+            ("ntellers".to_string(), test_config.ttbench_tellers),
+            ("nbranches".to_string(), test_config.ttbench_branches),
+            // ids are numbered from 0 and bounds are inclusive
+            ("max_aid".to_string(), test_config.ttbench_accounts - 1),
+            ("max_tid".to_string(), test_config.ttbench_tellers - 1),
+            ("max_bid".to_string(), test_config.ttbench_branches - 1),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name, Variable::Constant(Value::from(value))))
+        .collect::<Vec<_>>();
+        for (name, value) in file.variables {
+            let variable = value
+                .try_into::<VariableFile>()
+                .map_err(|err| anyhow!("variable '{name}': {err}"))?;
+            let variable = Variable::resolve(variable, &variables, bucket_count)
+                .map_err(|err| anyhow!("variable '{name}': {err}"))?;
+            if variables.iter().any(|(defined, _)| defined == &name) {
+                return Err(anyhow!("variable '{name}' is already defined"));
+            }
+            variables.push((name, variable));
+        }
+
+        if file.statements.is_empty() {
+            return Err(anyhow!("script has no statements"));
+        }
+        let statements = file
+            .statements
+            .into_iter()
+            .enumerate()
+            .map(|(index, statement)| {
+                Statement::resolve(statement, &variables)
+                    .map_err(|err| anyhow!("statement {}: {err}", index + 1))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Script {
+            name,
+            transaction: file.transaction,
+            variables,
+            statements,
+        })
+    }
+
+    /// Draws values of all variables for a single transaction.
+    pub fn generate(&self) -> Vec<Value> {
+        let mut rng = rand::thread_rng();
+        let mut values: Vec<Value> = Vec::with_capacity(self.variables.len());
+        for (_, variable) in &self.variables {
+            let value = match variable {
+                Variable::Constant(value) => value.clone(),
+                Variable::Uniform { min, max } => {
+                    let (min, max) = (min.get(&values), max.get(&values));
+                    Value::from(rng.gen_range(min..=max.max(min)))
+                }
+                Variable::Exponential {
+                    min,
+                    max,
+                    parameter,
+                } => {
+                    let min = min.get(&values);
+                    let max = max.get(&values).max(min);
+                    // Same as pgbench random_exponential.
+                    let cut = (-parameter).exp();
+                    let uniform = 1.0 - rng.gen::<f64>();
+                    let rand = -(cut + (1.0 - cut) * uniform).ln() / parameter;
+                    Value::from(min + ((max - min + 1) as f64 * rand) as i64)
+                }
+                Variable::Gaussian {
+                    min,
+                    max,
+                    parameter,
+                } => {
+                    let min = min.get(&values);
+                    let max = max.get(&values).max(min);
+                    // Same as pgbench random_gaussian, Box-Muller transform
+                    // repeated until the value is within the parameter.
+                    let stdev = loop {
+                        let rand1 = 1.0 - rng.gen::<f64>();
+                        let rand2 = 1.0 - rng.gen::<f64>();
+                        let stdev =
+                            (-2.0 * rand1.ln()).sqrt() * (2.0 * std::f64::consts::PI * rand2).sin();
+                        if -parameter <= stdev && stdev < *parameter {
+                            break stdev;
+                        }
+                    };
+                    let rand = (stdev + parameter) / (parameter * 2.0);
+                    Value::from(min + ((max - min + 1) as f64 * rand) as i64)
+                }
+                Variable::BucketId { of, bucket_count } => {
+                    let bucket_id = match &values[*of] {
+                        Value::String(of) => {
+                            calculate_bucket_id(of.as_str().unwrap_or_default(), *bucket_count)
+                        }
+                        of => calculate_bucket_id(of.to_string(), *bucket_count),
+                    };
+                    Value::from(bucket_id)
+                }
+                Variable::Uuid => Value::from(Uuid::new_v4().to_string()),
+                Variable::Now => Value::from(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .expect("Time went backwards")
+                        .as_secs(),
+                ),
+            };
+            values.push(value);
+        }
+        values
+    }
+}
+
+impl Variable {
+    fn resolve(
+        variable: VariableFile,
+        defined: &[(String, Variable)],
+        bucket_count: u32,
+    ) -> Result<Self, anyhow::Error> {
+        Ok(match variable {
+            VariableFile::Uniform { min, max } => {
+                let (min, max) = Bound::resolve_range(min, max, defined)?;
+                Variable::Uniform { min, max }
+            }
+            VariableFile::Exponential {
+                min,
+                max,
+                parameter,
+            } => {
+                if parameter <= 0.0 {
+                    return Err(anyhow!("exponential parameter must be greater than zero"));
+                }
+                let (min, max) = Bound::resolve_range(min, max, defined)?;
+                Variable::Exponential {
+                    min,
+                    max,
+                    parameter,
+                }
+            }
+            VariableFile::Gaussian {
+                min,
+                max,
+                parameter,
+            } => {
+                if parameter < 2.0 {
+                    return Err(anyhow!("gaussian parameter must be at least 2.0"));
+                }
+                let (min, max) = Bound::resolve_range(min, max, defined)?;
+                Variable::Gaussian {
+                    min,
+                    max,
+                    parameter,
+                }
+            }
+            VariableFile::BucketId { of } => Variable::BucketId {
+                of: reference(&of, defined)?,
+                bucket_count,
+            },
+            VariableFile::Uuid => Variable::Uuid,
+            VariableFile::Now => Variable::Now,
+        })
+    }
+}
+
+impl Bound {
+    fn resolve(bound: BoundFile, defined: &[(String, Variable)]) -> Result<Self, anyhow::Error> {
+        match bound {
+            BoundFile::Value(value) => Ok(Bound::Value(value)),
+            BoundFile::Variable(name) => Ok(Bound::Variable(reference(&name, defined)?)),
+        }
+    }
+
+    /// Resolves the bounds of a distribution. Bounds referencing variables
+    /// are checked when drawn: a `max` below `min` draws `min`.
+    fn resolve_range(
+        min: BoundFile,
+        max: BoundFile,
+        defined: &[(String, Variable)],
+    ) -> Result<(Self, Self), anyhow::Error> {
+        let (min, max) = (Self::resolve(min, defined)?, Self::resolve(max, defined)?);
+        if let (Bound::Value(min), Bound::Value(max)) = (&min, &max) {
+            if max < min {
+                return Err(anyhow!("max {max} is less than min {min}"));
+            }
+        }
+        Ok((min, max))
+    }
+
+    fn get(&self, values: &[Value]) -> i64 {
+        match self {
+            Bound::Value(value) => *value,
+            Bound::Variable(index) => values[*index].as_i64().unwrap_or_default(),
+        }
+    }
+}
+
+/// Index of the variable referenced as `":name"`.
+fn reference(name: &str, defined: &[(String, Variable)]) -> Result<usize, anyhow::Error> {
+    let name = name
+        .strip_prefix(':')
+        .ok_or_else(|| anyhow!("variable reference '{name}' must start with ':'"))?;
+    defined
+        .iter()
+        .position(|(defined, _)| defined == name)
+        .ok_or_else(|| anyhow!("unknown variable '{name}'"))
+}
+
+impl Statement {
+    fn resolve(
+        statement: StatementFile,
+        defined: &[(String, Variable)],
+    ) -> Result<Self, anyhow::Error> {
+        let array = |value: Option<toml::Value>, field: &str| match value {
+            Some(value @ toml::Value::Array(_)) => Template::resolve(value, defined),
+            Some(_) => Err(anyhow!("'{field}' must be an array")),
+            None => Ok(Template::Array(Vec::new())),
+        };
+        let required = |value: Option<toml::Value>, field: &str| match value {
+            Some(_) => array(value, field),
+            None => Err(anyhow!("'{field}' is required")),
+        };
+
+        let StatementFile {
+            call,
+            eval,
+            select,
+            insert,
+            replace,
+            update,
+            execute,
+            args,
+            key,
+            tuple,
+            operations,
+            binds,
+            index,
+            limit,
+        } = statement;
+        let operation = match (call, eval, select, insert, replace, update, execute) {
+            (Some(function), None, None, None, None, None, None) => Statement::Call {
+                function,
+                args: array(args, "args")?,
+            },
+            (None, Some(expr), None, None, None, None, None) => Statement::Eval {
+                expr,
+                args: array(args, "args")?,
+            },
+            (None, None, Some(space), None, None, None, None) => Statement::Select {
+                space,
+                index: index.unwrap_or_default(),
+                limit,
+                key: array(key, "key")?,
+            },
+            (None, None, None, Some(space), None, None, None) => Statement::Insert {
+                space,
+                tuple: required(tuple, "tuple")?,
+            },
+            (None, None, None, None, Some(space), None, None) => Statement::Replace {
+                space,
+                tuple: required(tuple, "tuple")?,
+            },
+            (None, None, None, None, None, Some(space), None) => Statement::Update {
+                space,
+                key: required(key, "key")?,
+                operations: required(operations, "operations")?,
+            },
+            (None, None, None, None, None, None, Some(query)) => Statement::Execute {
+                query,
+                binds: array(binds, "binds")?,
+            },
+            _ => {
+                return Err(anyhow!(
+                    "exactly one of 'call', 'eval', 'select', 'insert', 'replace', \
+                    'update' or 'execute' must be set"
+                ))
+            }
+        };
+        Ok(operation)
+    }
+}
+
+impl Template {
+    fn resolve(value: toml::Value, defined: &[(String, Variable)]) -> Result<Self, anyhow::Error> {
+        Ok(match value {
+            toml::Value::String(string) if string.starts_with(':') => {
+                Template::Variable(reference(&string, defined)?)
+            }
+            toml::Value::String(string) => Template::Value(Value::from(string)),
+            toml::Value::Integer(integer) => Template::Value(Value::from(integer)),
+            toml::Value::Float(float) => Template::Value(Value::from(float)),
+            toml::Value::Boolean(boolean) => Template::Value(Value::from(boolean)),
+            toml::Value::Datetime(datetime) => Template::Value(Value::from(datetime.to_string())),
+            toml::Value::Array(array) => Template::Array(
+                array
+                    .into_iter()
+                    .map(|value| Template::resolve(value, defined))
+                    .collect::<Result<_, _>>()?,
+            ),
+            toml::Value::Table(table) => Template::Map(
+                table
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, Template::resolve(value, defined)?)))
+                    .collect::<Result<_, anyhow::Error>>()?,
+            ),
+        })
+    }
+
+    pub fn render(&self, values: &[Value]) -> Value {
+        match self {
+            Template::Value(value) => value.clone(),
+            Template::Variable(index) => values[*index].clone(),
+            Template::Array(array) => {
+                Value::Array(array.iter().map(|item| item.render(values)).collect())
+            }
+            Template::Map(map) => Value::Map(
+                map.iter()
+                    .map(|(key, value)| (Value::from(key.as_str()), value.render(values)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Renders an array template as a list of request arguments.
+    pub fn render_args(&self, values: &[Value]) -> Vec<Value> {
+        match self.render(values) {
+            Value::Array(args) => args,
+            value => vec![value],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rmpv::Value;
+
+    use crate::config::TestConfig;
+
+    use super::{Script, Statement};
+
+    const SCRIPT: &str = r#"
+transaction = true
+
+[variables]
+aid = { type = "uniform", min = 0, max = ":max_aid" }
+delta = { type = "uniform", min = -5000, max = 5000 }
+bucket_id = { type = "bucket_id", of = ":aid" }
+
+[[statements]]
+call = "vshard.router.callrw"
+args = [":bucket_id", "box.space.ttbench_accounts:update", [":aid", [["+", "abalance", ":delta"]]]]
+
+[[statements]]
+select = "ttbench_accounts"
+key = [":aid"]
+"#;
+
+    #[test]
+    fn test_parse() {
+        let script = Script::parse("test".into(), SCRIPT, &TestConfig::default(), 30000).unwrap();
+        assert!(script.transaction);
+        assert_eq!(script.statements.len(), 2);
+        assert!(matches!(
+            &script.statements[1],
+            Statement::Select { space, index: 0, limit: None, .. } if space == "ttbench_accounts"
+        ));
+
+        for _ in 0..100 {
+            let values = script.generate();
+            // scale, naccounts, ntellers, nbranches, max_aid, max_tid,
+            // max_bid, aid, delta, bucket_id
+            assert_eq!(values.len(), 10);
+            assert_eq!(values[4], Value::from(99_999));
+            let aid = values[7].as_i64().unwrap();
+            assert!((0..100_000).contains(&aid));
+            let delta = values[8].as_i64().unwrap();
+            assert!((-5000..=5000).contains(&delta));
+            assert_eq!(
+                values[9].as_u64().unwrap() as u32,
+                crate::utils::calculate_bucket_id(aid.to_string(), 30000)
+            );
+
+            let Statement::Call { args, .. } = &script.statements[0] else {
+                unreachable!()
+            };
+            let args = args.render_args(&values);
+            assert_eq!(args[0], values[9]);
+            assert_eq!(args[1], Value::from("box.space.ttbench_accounts:update"));
+            assert_eq!(
+                args[2],
+                Value::Array(vec![
+                    values[7].clone(),
+                    Value::Array(vec![Value::Array(vec![
+                        Value::from("+"),
+                        Value::from("abalance"),
+                        values[8].clone()
+                    ])])
+                ])
+            );
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse = |content: &str| {
+            Script::parse("test".into(), content, &TestConfig::default(), 30000)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(parse("statements = []").contains("no statements"));
+        assert!(parse(
+            r#"
+[[statements]]
+call = "f"
+args = [":aid"]
+"#
+        )
+        .contains("unknown variable 'aid'"));
+        assert!(parse(
+            r#"
+[[statements]]
+call = "f"
+eval = "return 1"
+"#
+        )
+        .contains("exactly one of"));
+        assert!(parse(
+            r#"
+[variables]
+aid = { type = "zipfian", min = 1, max = 10 }
+
+[[statements]]
+call = "f"
+"#
+        )
+        .contains("variable 'aid'"));
+        assert!(parse(
+            r#"
+[[statements]]
+update = "ttbench_accounts"
+key = [1]
+"#
+        )
+        .contains("'operations' is required"));
+        assert!(parse(
+            r#"
+[variables]
+aid = { type = "exponential", min = 10, max = 1, parameter = 1.0 }
+
+[[statements]]
+call = "f"
+"#
+        )
+        .contains("max 1 is less than min 10"));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let script = Script::parse(
+            "test".into(),
+            r#"
+[variables]
+low = { type = "uniform", min = 5, max = 5 }
+exponential = { type = "exponential", min = 10, max = ":low", parameter = 1.0 }
+gaussian = { type = "gaussian", min = 10, max = ":low", parameter = 2.5 }
+tid = { type = "gaussian", min = 0, max = ":max_tid", parameter = 2.5 }
+
+[[statements]]
+call = "f"
+"#,
+            &TestConfig::default(),
+            30000,
+        )
+        .unwrap();
+        for _ in 0..100 {
+            let values = script.generate();
+            assert_eq!(values[8], Value::from(10));
+            assert_eq!(values[9], Value::from(10));
+            assert!((0..10).contains(&values[10].as_i64().unwrap()));
+        }
+    }
+}
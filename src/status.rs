@@ -1,12 +1,29 @@
 use std::{
-    fmt::Display,
-    sync::atomic::{AtomicU64, Ordering},
-    time::Duration,
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
 };
 
-use crate::config::{BenchMod, Config};
+use serde::Serialize;
 
+use crate::{
+    config::{Config, LogOptions},
+    job::error::{ErrorClass, FailedStatement},
+    utils::message,
+};
+
+use self::{histogram::Histogram, throttle::Throttle};
+
+pub mod histogram;
 pub mod iterations;
+pub mod log;
+pub mod statements;
+pub mod throttle;
+pub mod time;
 
 pub trait Target {
     type Output;
@@ -14,20 +31,54 @@ pub trait Target {
     fn new(config: &Config) -> Self;
     fn make_progress(&self) -> Result<Option<Self::Output>, anyhow::Error>;
     fn is_reached(&self) -> Result<bool, anyhow::Error>;
+
+    /// Time left until the target is reached, if it is known.
+    fn remaining(&self) -> Option<Duration> {
+        None
+    }
 }
 
 pub struct Status<T: Target + Send + Sync> {
     pub target: T,
     pub progress: Progress,
     pub logger: Logger,
+    pub throttle: Option<Throttle>,
+    /// transaction log options of `--log`
+    pub log: Option<LogOptions>,
 }
 
 impl<T: Target + Send + Sync> Status<T> {
+    pub fn new(target: T) -> Self {
+        Status {
+            target,
+            progress: Progress::default(),
+            logger: Logger::default(),
+            throttle: None,
+            log: None,
+        }
+    }
+
+    /// Waits until every job has finished: the target is reached and the
+    /// last tasks are done, or the jobs stopped with an error.
     pub async fn wait_the_end(&self) -> Result<(), anyhow::Error> {
+        let mut reported = Instant::now();
         loop {
             tokio::time::sleep(Duration::from_millis(100)).await;
-            self.progress.time_elapsed.fetch_add(100, Ordering::SeqCst);
-            if self.target.is_reached()? {
+            // Checked before the tick, which then collects the values of the
+            // last transactions.
+            let finished = self.progress.jobs_in_progress.load(Ordering::SeqCst) == 0;
+            self.progress.tick();
+            if let Some(interval) = self.logger.progress {
+                if reported.elapsed() >= interval {
+                    self.logger.progress(
+                        &self.progress,
+                        reported.elapsed(),
+                        self.target.remaining(),
+                    );
+                    reported = Instant::now();
+                }
+            }
+            if finished {
                 return Ok(());
             }
         }
@@ -46,77 +97,466 @@ impl<T: Target + Send + Sync> Status<T> {
     pub fn is_reached(&self) -> Result<bool, anyhow::Error> {
         self.target.is_reached()
     }
+
+    /// Start time of the next transaction when the load is rate limited.
+    pub fn schedule(&self) -> Option<Instant> {
+        self.throttle.as_ref().map(Throttle::schedule)
+    }
+
+    pub fn is_late(&self, scheduled: Instant) -> bool {
+        self.throttle
+            .as_ref()
+            .is_some_and(|throttle| throttle.is_late(scheduled))
+    }
 }
 
 impl<'a, T: Target + Send + Sync> TryFrom<&'a Config> for Status<T> {
     type Error = anyhow::Error;
 
     fn try_from(config: &'a Config) -> Result<Self, Self::Error> {
-        match config.mode {
-            BenchMod::Iterations => Ok(Status {
-                target: T::new(config),
-                progress: Progress::default(),
-                logger: Logger,
-            }),
-            BenchMod::Time => todo!(),
-        }
+        Ok(Status {
+            target: T::new(config),
+            progress: Progress::default(),
+            logger: Logger::new(config),
+            throttle: config
+                .rate
+                .map(|rate| Throttle::new(rate, config.latency_limit)),
+            log: config.log.clone(),
+        })
     }
 }
 
-pub struct Logger;
+#[derive(Default)]
+pub struct Logger {
+    /// Interval between progress reports.
+    progress: Option<Duration>,
+    verbose_errors: bool,
+}
 
 impl Logger {
-    pub fn error<D: Display>(&self, text: D) {
-        println!("{text}")
+    pub fn new(config: &Config) -> Self {
+        Self {
+            progress: config.progress,
+            verbose_errors: config.verbose_errors,
+        }
+    }
+
+    /// Prints the error of a failed task, with `--verbose-errors` also the
+    /// job, the values of the task and the failed statement.
+    pub fn error<V: Debug>(&self, job: u64, value: Option<&V>, err: &anyhow::Error) {
+        let cause = err.root_cause();
+        if !self.verbose_errors {
+            message!("{cause}");
+            return;
+        }
+        let statement = match err.downcast_ref::<FailedStatement>() {
+            Some(statement) => format!(" in {statement}"),
+            None => String::new(),
+        };
+        match value {
+            Some(value) => message!("job {job} failed{statement}: {cause}, values: {value:?}"),
+            None => message!("job {job} failed{statement}: {cause}"),
+        }
+    }
+
+    /// Prints statistics collected since the previous report.
+    pub fn progress(&self, progress: &Progress, interval: Duration, remaining: Option<Duration>) {
+        message!("{}", Self::interval_report(progress, interval, remaining))
+    }
+
+    /// Line of the progress report, resets the interval statistics.
+    fn interval_report(
+        progress: &Progress,
+        interval: Duration,
+        remaining: Option<Duration>,
+    ) -> String {
+        let latency = progress.interval_latency.take();
+        let failed = progress.interval_failures.swap(0, Ordering::Relaxed);
+        let mut report = format!(
+            "progress: {:.1} s, {:.1} tps, lat {:.3} ms stddev {:.3}, {failed} failed",
+            progress.started.elapsed().as_secs_f64(),
+            latency.count() as f64 / interval.as_secs_f64(),
+            as_millis_f64(latency.mean()),
+            as_millis_f64(latency.stddev()),
+        );
+        let lag = progress.interval_lag.take();
+        if lag.count() > 0 {
+            report.push_str(&format!(", lag {:.3} ms", as_millis_f64(lag.mean())));
+        }
+        let skipped = progress.interval_skipped.swap(0, Ordering::Relaxed);
+        if skipped > 0 {
+            report.push_str(&format!(", {skipped} skipped"));
+        }
+        if let Some(remaining) = remaining {
+            report.push_str(&format!(", {} s left", remaining.as_secs()));
+        }
+        report
     }
 }
 
-#[derive(Default)]
 pub struct Progress {
     iterations_count: AtomicU64,
-    /// iterations per second
-    iterations: f64,
-    iteration_duration_avg: Duration,
-    iteration_duration_p95: Duration,
-    iteration_duration_max: Duration,
+    failures_count: AtomicU64,
+    /// failures and retries by `ErrorClass`
+    class_failures: [AtomicU64; ErrorClass::ALL.len()],
+    class_retries: [AtomicU64; ErrorClass::ALL.len()],
+    /// transactions retried at least once
+    retried_count: AtomicU64,
+    /// failures by `error_code`
+    code_failures: Mutex<BTreeMap<String, u64>>,
+    skipped_count: AtomicU64,
+    /// transactions which insert a history row, started and successful
+    history_started_count: AtomicU64,
+    history_written_count: AtomicU64,
+    latency: Histogram,
+    /// delay between scheduled and actual start of rate limited transactions
+    lag: Histogram,
+    /// values since the last tick, which merges them into the totals and
+    /// the interval ones
+    recent_latency: Histogram,
+    recent_lag: Histogram,
+    /// values since the last progress report
+    interval_failures: AtomicU64,
+    interval_skipped: AtomicU64,
+    interval_latency: Histogram,
+    interval_lag: Histogram,
+    started: Instant,
+    /// wall time since start in milliseconds
     time_elapsed: AtomicU64,
-    time_estimate: Duration,
-    jobs_in_progress: u64,
+    jobs_in_progress: AtomicU64,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            iterations_count: AtomicU64::default(),
+            failures_count: AtomicU64::default(),
+            class_failures: Default::default(),
+            class_retries: Default::default(),
+            retried_count: AtomicU64::default(),
+            code_failures: Mutex::default(),
+            skipped_count: AtomicU64::default(),
+            history_started_count: AtomicU64::default(),
+            history_written_count: AtomicU64::default(),
+            latency: Histogram::default(),
+            lag: Histogram::default(),
+            recent_latency: Histogram::default(),
+            recent_lag: Histogram::default(),
+            interval_failures: AtomicU64::default(),
+            interval_skipped: AtomicU64::default(),
+            interval_latency: Histogram::default(),
+            interval_lag: Histogram::default(),
+            started: Instant::now(),
+            time_elapsed: AtomicU64::default(),
+            jobs_in_progress: AtomicU64::default(),
+        }
+    }
 }
 
 impl Progress {
-    pub fn update(&mut self, exec_time: Duration) -> Result<(), anyhow::Error> {
-        Ok(())
+    pub fn job_started(&self) {
+        self.jobs_in_progress.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn job_finished(&self) {
+        self.jobs_in_progress.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Records the execution time of a successful transaction.
+    pub fn update(&self, exec_time: Duration) {
+        self.recent_latency.record(exec_time);
+    }
+
+    pub fn fail(&self, class: ErrorClass, code: String) {
+        self.failures_count.fetch_add(1, Ordering::Relaxed);
+        self.class_failures[class.index()].fetch_add(1, Ordering::Relaxed);
+        *self.code_failures.lock().unwrap().entry(code).or_default() += 1;
+        self.interval_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a retry of a transaction failed with a retryable error.
+    pub fn retry(&self, class: ErrorClass, first: bool) {
+        self.class_retries[class.index()].fetch_add(1, Ordering::Relaxed);
+        if first {
+            self.retried_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.failures_count.load(Ordering::Relaxed)
+    }
+
+    /// Failures by box error code or vshard error name.
+    pub fn failures_detailed(&self) -> BTreeMap<String, u64> {
+        self.code_failures.lock().unwrap().clone()
+    }
+
+    /// Counts a started transaction which inserts a history row.
+    pub fn history_started(&self) {
+        self.history_started_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a successful transaction which inserted a history row.
+    pub fn history_written(&self) {
+        self.history_written_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Transactions which insert a history row: started and successful.
+    pub fn history_transactions(&self) -> (u64, u64) {
+        (
+            self.history_started_count.load(Ordering::Relaxed),
+            self.history_written_count.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Records the schedule lag of a rate limited transaction.
+    pub fn lag(&self, lag: Duration) {
+        self.recent_lag.record(lag);
+    }
+
+    /// Counts a transaction skipped because of `--latency-limit`.
+    pub fn skip(&self) {
+        self.skipped_count.fetch_add(1, Ordering::Relaxed);
+        self.interval_skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn tick(&self) {
+        let latency = self.recent_latency.take();
+        self.latency.merge(&latency);
+        self.interval_latency.merge(&latency);
+        let lag = self.recent_lag.take();
+        self.lag.merge(&lag);
+        self.interval_lag.merge(&lag);
+        self.time_elapsed
+            .store(self.started.elapsed().as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+/// Results of a run for `--output`.
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    pub duration_s: f64,
+    pub processed: u64,
+    pub failed: u64,
+    pub skipped: u64,
+    /// transactions retried at least once
+    pub retried: u64,
+    pub retries: u64,
+    pub tps: f64,
+    pub latency_ms: Latency,
+    /// schedule lag with `--rate`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lag_ms: Option<Lag>,
+    pub failures_by_class: BTreeMap<String, u64>,
+    pub retries_by_class: BTreeMap<String, u64>,
+    pub failures_by_code: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Latency {
+    pub average: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    #[serde(rename = "p99.9")]
+    pub p99_9: f64,
+    pub max: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Lag {
+    pub average: f64,
+    pub max: f64,
+}
+
+impl Progress {
+    pub fn summary(&self) -> Summary {
+        let elapsed = Duration::from_millis(self.time_elapsed.load(Ordering::Relaxed));
+        let processed = self.latency.count();
+        let by_class = |counters: &[AtomicU64; ErrorClass::ALL.len()]| {
+            ErrorClass::ALL
+                .iter()
+                .map(|class| {
+                    let count = counters[class.index()].load(Ordering::Relaxed);
+                    (class.to_string(), count)
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+        let retries_by_class = by_class(&self.class_retries);
+        let quantile = |quantile| as_millis_f64(self.latency.quantile(quantile));
+        Summary {
+            duration_s: elapsed.as_secs_f64(),
+            processed,
+            failed: self.failures(),
+            skipped: self.skipped_count.load(Ordering::Relaxed),
+            retried: self.retried_count.load(Ordering::Relaxed),
+            retries: retries_by_class.values().sum(),
+            tps: match elapsed.as_secs_f64() {
+                secs if secs > 0.0 => processed as f64 / secs,
+                _ => 0.0,
+            },
+            latency_ms: Latency {
+                average: as_millis_f64(self.latency.mean()),
+                stddev: as_millis_f64(self.latency.stddev()),
+                min: as_millis_f64(self.latency.min()),
+                p50: quantile(0.5),
+                p90: quantile(0.9),
+                p95: quantile(0.95),
+                p99: quantile(0.99),
+                p99_9: quantile(0.999),
+                max: as_millis_f64(self.latency.max()),
+            },
+            lag_ms: (self.lag.count() > 0).then(|| Lag {
+                average: as_millis_f64(self.lag.mean()),
+                max: as_millis_f64(self.lag.max()),
+            }),
+            failures_by_class: by_class(&self.class_failures),
+            retries_by_class,
+            failures_by_code: self.failures_detailed(),
+        }
     }
 }
 
+fn as_millis_f64(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
 impl Display for Progress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
+        let elapsed = Duration::from_millis(self.time_elapsed.load(Ordering::Relaxed));
+        let processed = self.latency.count();
+        let failed = self.failures_count.load(Ordering::Relaxed);
+        writeln!(f, "duration: {:.3} s", elapsed.as_secs_f64())?;
+        writeln!(f, "number of transactions actually processed: {processed}")?;
+        writeln!(
             f,
-            "duration: {} s",
-            self.time_elapsed.load(Ordering::Relaxed)
+            "number of failed transactions: {failed} ({:.3}%)",
+            match processed + failed {
+                0 => 0.0,
+                total => failed as f64 * 100.0 / total as f64,
+            }
         )?;
-        write!(
+        let retried = self.retried_count.load(Ordering::Relaxed);
+        if retried > 0 {
+            let retries: u64 = self
+                .class_retries
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .sum();
+            writeln!(
+                f,
+                "number of transactions retried: {retried} ({:.3}%)",
+                retried as f64 * 100.0 / (processed + failed) as f64
+            )?;
+            writeln!(f, "total number of retries: {retries}")?;
+        }
+        for class in ErrorClass::ALL {
+            let failed = self.class_failures[class.index()].load(Ordering::Relaxed);
+            let retries = self.class_retries[class.index()].load(Ordering::Relaxed);
+            if failed > 0 || retries > 0 {
+                writeln!(f, "{class} errors: {failed} failed, {retries} retries")?;
+            }
+        }
+        let skipped = self.skipped_count.load(Ordering::Relaxed);
+        if self.lag.count() > 0 || skipped > 0 {
+            writeln!(
+                f,
+                "number of transactions skipped: {skipped} ({:.3}%)",
+                skipped as f64 * 100.0 / (processed + failed + skipped) as f64
+            )?;
+        }
+        writeln!(
             f,
-            "number or transactions actualy processed: {}",
-            self.iterations_count.load(Ordering::Relaxed)
+            "latency average = {:.3} ms",
+            as_millis_f64(self.latency.mean())
         )?;
-        write!(
+        writeln!(
             f,
-            "latency average: {} ms",
-            self.iteration_duration_avg.as_millis()
+            "latency stddev = {:.3} ms",
+            as_millis_f64(self.latency.stddev())
         )?;
-        write!(
+        for (name, quantile) in [
+            ("p50", 0.5),
+            ("p90", 0.9),
+            ("p95", 0.95),
+            ("p99", 0.99),
+            ("p99.9", 0.999),
+        ] {
+            writeln!(
+                f,
+                "latency {name} = {:.3} ms",
+                as_millis_f64(self.latency.quantile(quantile))
+            )?;
+        }
+        writeln!(
             f,
-            "latency p95: {} ms",
-            self.iteration_duration_p95.as_millis()
+            "latency max = {:.3} ms",
+            as_millis_f64(self.latency.max())
         )?;
+        if self.lag.count() > 0 {
+            writeln!(
+                f,
+                "rate limit schedule lag: avg {:.3} (max {:.3}) ms",
+                as_millis_f64(self.lag.mean()),
+                as_millis_f64(self.lag.max())
+            )?;
+        }
         write!(
             f,
-            "latency max: {} ms",
-            self.iteration_duration_max.as_millis()
-        )?;
-        write!(f, "tps = {}", self.iterations)
+            "tps = {:.6}",
+            match elapsed.as_secs_f64() {
+                secs if secs > 0.0 => processed as f64 / secs,
+                _ => 0.0,
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::job::error::ErrorClass;
+
+    use super::{Logger, Progress};
+
+    #[test]
+    fn test_interval_report() {
+        let progress = Progress::default();
+        for millis in [10, 20, 30] {
+            progress.update(Duration::from_millis(millis));
+        }
+        progress.lag(Duration::from_millis(4));
+        progress.skip();
+        progress.fail(ErrorClass::Conflict, "box error 97".into());
+        progress.tick();
+
+        let report = Logger::interval_report(
+            &progress,
+            Duration::from_secs(2),
+            Some(Duration::from_secs(7)),
+        );
+        assert!(report.starts_with("progress: "), "{report}");
+        assert!(
+            report.contains(", 1.5 tps, lat 20.000 ms stddev "),
+            "{report}"
+        );
+        assert!(
+            report.ends_with(", 1 failed, lag 4.000 ms, 1 skipped, 7 s left"),
+            "{report}"
+        );
+
+        // The interval statistics are reset, the totals are kept.
+        let report = Logger::interval_report(&progress, Duration::from_secs(2), None);
+        assert!(
+            report.ends_with(", 0.0 tps, lat 0.000 ms stddev 0.000, 0 failed"),
+            "{report}"
+        );
+        assert_eq!(progress.summary().processed, 3);
+        assert_eq!(progress.failures(), 1);
+        assert_eq!(progress.summary().skipped, 1);
     }
 }
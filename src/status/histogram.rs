@@ -0,0 +1,284 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Values below `SUB_BUCKETS` are stored exactly, larger ones are stored in
+/// log-linear buckets with `SUB_BUCKETS / 2` linear steps per power of two,
+/// which keeps the relative error under 1%.
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const HALF_SUB_BUCKETS: usize = SUB_BUCKETS / 2;
+/// Number of powers of two above `SUB_BUCKETS`, a few years in µs.
+const MAGNITUDES: usize = 40;
+const BUCKETS: usize = SUB_BUCKETS + MAGNITUDES * HALF_SUB_BUCKETS;
+
+/// Lock-free latency histogram in HDR style. Values are recorded in
+/// microseconds.
+pub struct Histogram {
+    counts: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    pub fn record(&self, value: Duration) {
+        let value = value.as_micros().min(u64::MAX as u128) as u64;
+        // Counted before the bucket, so `take` never leaves more values in
+        // the buckets than in the count.
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.counts[index(value)].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.min.fetch_min(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// Moves recorded values into a new histogram, leaving this one empty.
+    /// The count of the taken values is the sum of the swapped buckets, so a
+    /// value recorded concurrently is counted in exactly one histogram.
+    pub fn take(&self) -> Histogram {
+        let counts: Box<[AtomicU64]> = self
+            .counts
+            .iter()
+            .map(|bucket| AtomicU64::new(bucket.swap(0, Ordering::Relaxed)))
+            .collect();
+        let count = counts
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum();
+        self.count.fetch_sub(count, Ordering::Relaxed);
+        Histogram {
+            counts,
+            count: AtomicU64::new(count),
+            sum: AtomicU64::new(self.sum.swap(0, Ordering::Relaxed)),
+            min: AtomicU64::new(self.min.swap(u64::MAX, Ordering::Relaxed)),
+            max: AtomicU64::new(self.max.swap(0, Ordering::Relaxed)),
+        }
+    }
+
+    /// Adds the values recorded by another histogram.
+    pub fn merge(&self, other: &Histogram) {
+        for (bucket, other) in self.counts.iter().zip(other.counts.iter()) {
+            let count = other.load(Ordering::Relaxed);
+            if count > 0 {
+                bucket.fetch_add(count, Ordering::Relaxed);
+            }
+        }
+        self.count
+            .fetch_add(other.count.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.sum
+            .fetch_add(other.sum.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.min
+            .fetch_min(other.min.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max
+            .fetch_max(other.max.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn min(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            _ => Duration::from_micros(self.min.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max.load(Ordering::Relaxed))
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            count => Duration::from_micros(self.sum.load(Ordering::Relaxed) / count),
+        }
+    }
+
+    /// Standard deviation, calculated from bucket values.
+    pub fn stddev(&self) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let mean = self.sum.load(Ordering::Relaxed) as f64 / count as f64;
+        let variance = self
+            .counts
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| {
+                let deviation = value_at(index) as f64 - mean;
+                bucket.load(Ordering::Relaxed) as f64 * deviation * deviation
+            })
+            .sum::<f64>()
+            / count as f64;
+        Duration::from_micros(variance.sqrt() as u64)
+    }
+
+    /// Value below which `quantile` (0.0..=1.0) of the recorded values fall.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((quantile * count as f64).ceil() as u64).clamp(1, count);
+        let mut seen = 0;
+        for (index, bucket) in self.counts.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                return Duration::from_micros(value_at(index)).clamp(self.min(), self.max());
+            }
+        }
+        self.max()
+    }
+}
+
+fn index(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+    let magnitude = (63 - value.leading_zeros() - (SUB_BUCKET_BITS - 1)) as usize;
+    if magnitude > MAGNITUDES {
+        return BUCKETS - 1;
+    }
+    let sub_bucket = (value >> magnitude) as usize - HALF_SUB_BUCKETS;
+    SUB_BUCKETS + (magnitude - 1) * HALF_SUB_BUCKETS + sub_bucket
+}
+
+/// Middle of the range of values that fall into the bucket.
+fn value_at(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let magnitude = (index - SUB_BUCKETS) / HALF_SUB_BUCKETS + 1;
+    let sub_bucket = ((index - SUB_BUCKETS) % HALF_SUB_BUCKETS + HALF_SUB_BUCKETS) as u64;
+    (sub_bucket << magnitude) + (1 << (magnitude - 1))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::atomic::Ordering, time::Duration};
+
+    use super::{index, value_at, Histogram, BUCKETS};
+
+    #[test]
+    fn test_index() {
+        assert_eq!(index(0), 0);
+        assert_eq!(index(127), 127);
+        assert_eq!(index(128), 128);
+        assert_eq!(index(129), 128);
+        assert_eq!(index(130), 129);
+        assert_eq!(index(u64::MAX), BUCKETS - 1);
+
+        for value in [200, 1_000, 12_345, 1_000_000, 987_654_321] {
+            let error = value_at(index(value)).abs_diff(value) as f64 / value as f64;
+            assert!(error < 0.01, "{value}: {error}");
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let histogram = Histogram::default();
+        assert_eq!(histogram.quantile(0.99), Duration::ZERO);
+        assert_eq!(histogram.mean(), Duration::ZERO);
+
+        for value in 1..=1000 {
+            histogram.record(Duration::from_micros(value));
+        }
+
+        assert_eq!(histogram.count(), 1000);
+        assert_eq!(histogram.min(), Duration::from_micros(1));
+        assert_eq!(histogram.max(), Duration::from_micros(1000));
+        assert_eq!(histogram.mean(), Duration::from_micros(500));
+        let stddev = histogram.stddev().as_micros();
+        assert!((285..=293).contains(&stddev), "{stddev}");
+        for (quantile, expected) in [(0.5, 500), (0.9, 900), (0.99, 990), (1.0, 1000)] {
+            let value = histogram.quantile(quantile).as_micros() as f64;
+            assert!((value - expected as f64).abs() / (expected as f64) < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_take() {
+        let histogram = Histogram::default();
+        histogram.record(Duration::from_micros(10));
+        histogram.record(Duration::from_micros(30));
+
+        let taken = histogram.take();
+        assert_eq!(taken.count(), 2);
+        assert_eq!(taken.mean(), Duration::from_micros(20));
+        assert_eq!(taken.min(), Duration::from_micros(10));
+        assert_eq!(taken.max(), Duration::from_micros(30));
+
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.max(), Duration::ZERO);
+        histogram.record(Duration::from_micros(50));
+        assert_eq!(histogram.min(), Duration::from_micros(50));
+        assert_eq!(histogram.quantile(0.5), Duration::from_micros(50));
+    }
+
+    #[test]
+    fn test_take_concurrently() {
+        let histogram = Histogram::default();
+        let total = Histogram::default();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for value in 0..10_000 {
+                        histogram.record(Duration::from_micros(value % 500));
+                    }
+                });
+            }
+            for _ in 0..100 {
+                let taken = histogram.take();
+                let buckets: u64 = taken
+                    .counts
+                    .iter()
+                    .map(|bucket| bucket.load(Ordering::Relaxed))
+                    .sum();
+                assert_eq!(taken.count(), buckets);
+                total.merge(&taken);
+            }
+        });
+        total.merge(&histogram.take());
+
+        assert_eq!(total.count(), 40_000);
+        assert_eq!(histogram.count(), 0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let first = Histogram::default();
+        let second = Histogram::default();
+        for value in 1..=500 {
+            first.record(Duration::from_micros(value));
+        }
+        for value in 501..=1000 {
+            second.record(Duration::from_micros(value));
+        }
+
+        first.merge(&second);
+        assert_eq!(first.count(), 1000);
+        assert_eq!(first.min(), Duration::from_micros(1));
+        assert_eq!(first.max(), Duration::from_micros(1000));
+        assert_eq!(first.mean(), Duration::from_micros(500));
+        let median = first.quantile(0.5).as_micros() as f64;
+        assert!((median - 500.0).abs() / 500.0 < 0.01, "{median}");
+        assert_eq!(second.count(), 500);
+    }
+}
@@ -8,7 +8,7 @@ use std::{
 
 use crate::config::Config;
 
-use super::{Logger, Progress, Status, Target};
+use super::{Status, Target};
 
 pub struct Iterations<T> {
     pub generator: T,
@@ -53,10 +53,6 @@ impl Target for Iterations<IncreasingUsize> {
 
 impl Status<Iterations<IncreasingUsize>> {
     pub fn single_run() -> Self {
-        Status {
-            target: Iterations::new(AtomicU64::new(0), Arc::new(Mutex::new(1))),
-            progress: Progress::default(),
-            logger: Logger,
-        }
+        Status::new(Iterations::new(AtomicU64::new(0), Arc::new(Mutex::new(1))))
     }
 }
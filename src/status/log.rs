@@ -0,0 +1,203 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use rand::Rng;
+
+use crate::config::LogOptions;
+
+/// How a logged transaction ended.
+pub enum Outcome {
+    Done(Duration),
+    /// Failed with the given `error_code`.
+    Failed(String),
+    /// Skipped because of `--latency-limit`.
+    Skipped,
+}
+
+/// Transaction log of a job, written to `<prefix>.<pid>.<job id>`. Each line
+/// is
+///
+/// ```text
+/// job_id transaction_no latency_us lag_us script_id error epoch_s epoch_us
+/// ```
+///
+/// where the latency is `failed` or `skipped` for such transactions, the lag
+/// is 0 without `--rate` and the error is `-` for successful ones. With
+/// `--aggregate-interval` each line is an interval instead:
+///
+/// ```text
+/// interval_start count sum_us sum2_us min_us max_us failures skipped
+/// ```
+pub struct TransactionLog {
+    writer: BufWriter<File>,
+    job: u64,
+    transactions: u64,
+    sampling_rate: Option<f64>,
+    aggregate: Option<Aggregate>,
+}
+
+#[derive(Default)]
+struct Aggregate {
+    /// interval length in seconds
+    interval: u64,
+    /// epoch seconds of the interval start, 0 before the first transaction
+    start: u64,
+    count: u64,
+    sum: u64,
+    sum2: u128,
+    min: u64,
+    max: u64,
+    failures: u64,
+    skipped: u64,
+}
+
+impl TransactionLog {
+    pub fn create(options: &LogOptions, job: u64) -> Result<Self, anyhow::Error> {
+        let path = format!("{}.{}.{job}", options.prefix, std::process::id());
+        let file =
+            File::create(&path).map_err(|err| anyhow!("failed to create log {path}: {err}"))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            job,
+            transactions: 0,
+            sampling_rate: options.sampling_rate,
+            aggregate: options.aggregate_interval.map(|interval| Aggregate {
+                interval,
+                ..Default::default()
+            }),
+        })
+    }
+
+    pub fn record(
+        &mut self,
+        script: usize,
+        outcome: Outcome,
+        lag: Option<Duration>,
+    ) -> Result<(), anyhow::Error> {
+        let transaction = self.transactions;
+        self.transactions += 1;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+
+        if let Some(aggregate) = self.aggregate.as_mut() {
+            aggregate.advance(&mut self.writer, now.as_secs())?;
+            aggregate.add(&outcome);
+            return Ok(());
+        }
+
+        if let Some(rate) = self.sampling_rate {
+            if !rand::thread_rng().gen_bool(rate) {
+                return Ok(());
+            }
+        }
+        let (latency, error) = match outcome {
+            Outcome::Done(latency) => (latency.as_micros().to_string(), "-".to_string()),
+            Outcome::Failed(code) => ("failed".to_string(), code.replace(' ', "_")),
+            Outcome::Skipped => ("skipped".to_string(), "-".to_string()),
+        };
+        writeln!(
+            self.writer,
+            "{} {transaction} {latency} {} {script} {error} {} {}",
+            self.job,
+            lag.unwrap_or_default().as_micros(),
+            now.as_secs(),
+            now.subsec_micros()
+        )?;
+        Ok(())
+    }
+
+    /// Writes the last aggregate interval and flushes the file.
+    pub fn finish(&mut self) -> Result<(), anyhow::Error> {
+        if let Some(aggregate) = self.aggregate.as_mut() {
+            if aggregate.start > 0 {
+                aggregate.write(&mut self.writer)?;
+            }
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl Aggregate {
+    /// Writes the intervals which ended before `now`, empty ones included.
+    fn advance(&mut self, writer: &mut impl Write, now: u64) -> Result<(), anyhow::Error> {
+        if self.start == 0 {
+            self.start = now;
+        }
+        while now >= self.start + self.interval {
+            self.write(writer)?;
+            *self = Aggregate {
+                interval: self.interval,
+                start: self.start + self.interval,
+                ..Default::default()
+            };
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, outcome: &Outcome) {
+        match outcome {
+            Outcome::Done(latency) => {
+                let latency = latency.as_micros().min(u64::MAX as u128) as u64;
+                self.min = match self.count {
+                    0 => latency,
+                    _ => self.min.min(latency),
+                };
+                self.max = self.max.max(latency);
+                self.count += 1;
+                self.sum += latency;
+                self.sum2 += latency as u128 * latency as u128;
+            }
+            Outcome::Failed(_) => self.failures += 1,
+            Outcome::Skipped => self.skipped += 1,
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write) -> Result<(), anyhow::Error> {
+        writeln!(
+            writer,
+            "{} {} {} {} {} {} {} {}",
+            self.start,
+            self.count,
+            self.sum,
+            self.sum2,
+            self.min,
+            self.max,
+            self.failures,
+            self.skipped
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{Aggregate, Outcome};
+
+    #[test]
+    fn test_aggregate() {
+        let mut aggregate = Aggregate {
+            interval: 10,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+
+        aggregate.advance(&mut out, 100).unwrap();
+        aggregate.add(&Outcome::Done(Duration::from_micros(300)));
+        aggregate.add(&Outcome::Done(Duration::from_micros(100)));
+        aggregate.add(&Outcome::Failed("box error 97".into()));
+        aggregate.advance(&mut out, 125).unwrap();
+        aggregate.add(&Outcome::Skipped);
+        aggregate.write(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "100 2 400 100000 100 300 1 0\n110 0 0 0 0 0 0 0\n120 0 0 0 0 0 0 1\n"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::{config::Config, job::error::FailedStatement};
+
+/// Latencies and failures of every statement of every workload, collected
+/// with `--report-per-statement`.
+#[derive(Default)]
+pub struct Statements {
+    enabled: bool,
+    workloads: Mutex<Vec<WorkloadStatements>>,
+}
+
+struct WorkloadStatements {
+    name: String,
+    statements: Vec<StatementStats>,
+}
+
+#[derive(Default)]
+struct StatementStats {
+    name: String,
+    count: u64,
+    /// total latency in microseconds
+    sum: u64,
+    failures: u64,
+}
+
+impl Statements {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            enabled: config.report_per_statement,
+            ..Default::default()
+        }
+    }
+
+    /// Starts numbering the statements of a transaction of the workload.
+    pub fn transaction<'a>(&'a self, workload: &'a str) -> Recorder<'a> {
+        Recorder {
+            statements: self,
+            workload,
+            index: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Statement latencies and failures for `--output`.
+    pub fn summary(&self) -> Vec<WorkloadSummary> {
+        self.workloads
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|workload| WorkloadSummary {
+                workload: workload.name.clone(),
+                statements: workload
+                    .statements
+                    .iter()
+                    .map(|stats| StatementSummary {
+                        name: stats.name.clone(),
+                        count: stats.count,
+                        average_ms: stats.average_ms(),
+                        failures: stats.failures,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn record<N: Display>(
+        &self,
+        workload: &str,
+        index: usize,
+        name: N,
+        elapsed: Duration,
+        ok: bool,
+    ) {
+        let mut workloads = self.workloads.lock().unwrap();
+        let position = match workloads.iter().position(|stats| stats.name == workload) {
+            Some(position) => position,
+            None => {
+                workloads.push(WorkloadStatements {
+                    name: workload.to_string(),
+                    statements: Vec::new(),
+                });
+                workloads.len() - 1
+            }
+        };
+        let statements = &mut workloads[position].statements;
+        if statements.len() <= index {
+            statements.resize_with(index + 1, Default::default);
+        }
+        let stats = &mut statements[index];
+        if stats.name.is_empty() {
+            stats.name = name.to_string();
+        }
+        stats.count += 1;
+        stats.sum += elapsed.as_micros().min(u64::MAX as u128) as u64;
+        if !ok {
+            stats.failures += 1;
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct WorkloadSummary {
+    pub workload: String,
+    pub statements: Vec<StatementSummary>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StatementSummary {
+    pub name: String,
+    pub count: u64,
+    pub average_ms: f64,
+    pub failures: u64,
+}
+
+impl StatementStats {
+    fn average_ms(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            count => self.sum as f64 / count as f64 / 1000.0,
+        }
+    }
+}
+
+impl Display for Statements {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "statement latencies in milliseconds and failures:")?;
+        for workload in self.workloads.lock().unwrap().iter() {
+            write!(f, "\n{}:", workload.name)?;
+            for stats in &workload.statements {
+                write!(
+                    f,
+                    "\n{:>12.3} {:>10}  {}",
+                    stats.average_ms(),
+                    stats.failures,
+                    stats.name
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Numbers, times and counts the statements of one transaction.
+pub struct Recorder<'a> {
+    statements: &'a Statements,
+    workload: &'a str,
+    index: usize,
+}
+
+impl Recorder<'_> {
+    /// Executes the next statement, its error is marked with the index of the
+    /// statement.
+    pub async fn run<T, E, F, N>(&mut self, name: N, statement: F) -> Result<T, anyhow::Error>
+    where
+        E: Into<anyhow::Error>,
+        F: Future<Output = Result<T, E>>,
+        N: Display,
+    {
+        let index = self.index;
+        self.index += 1;
+        let started = Instant::now();
+        let result = statement.await.map_err(Into::into);
+        if self.statements.enabled {
+            self.statements.record(
+                self.workload,
+                index,
+                name,
+                started.elapsed(),
+                result.is_ok(),
+            );
+        }
+        result.context(FailedStatement(index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+
+    use crate::job::error::FailedStatement;
+
+    use super::Statements;
+
+    #[test]
+    fn test_recorder() {
+        let statements = Statements {
+            enabled: true,
+            ..Default::default()
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            let mut recorder = statements.transaction("tpcb-like");
+            runtime
+                .block_on(recorder.run("begin", async { Ok::<_, anyhow::Error>(()) }))
+                .unwrap();
+            let err = runtime
+                .block_on(recorder.run("update", async { Err::<(), _>(anyhow!("conflict")) }))
+                .unwrap_err();
+            assert_eq!(err.downcast_ref::<FailedStatement>().unwrap().0, 1);
+        }
+
+        let workloads = statements.workloads.lock().unwrap();
+        let stats = &workloads[0].statements;
+        assert_eq!(workloads[0].name, "tpcb-like");
+        assert_eq!(
+            stats
+                .iter()
+                .map(|stats| stats.name.as_str())
+                .collect::<Vec<_>>(),
+            ["begin", "update"]
+        );
+        assert_eq!((stats[0].count, stats[0].failures), (2, 0));
+        assert_eq!((stats[1].count, stats[1].failures), (2, 2));
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+
+/// Schedules transaction start times on a Poisson process shared by all jobs.
+pub struct Throttle {
+    /// transactions per second
+    rate: f64,
+    /// transactions starting later than this are skipped
+    latency_limit: Option<Duration>,
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub fn new(rate: f64, latency_limit: Option<Duration>) -> Self {
+        Self {
+            rate,
+            latency_limit,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Returns the start time of the next transaction.
+    pub fn schedule(&self) -> Instant {
+        let mut next = self.next.lock().unwrap();
+        let scheduled = *next;
+        // Exponentially distributed interval between transactions.
+        let uniform: f64 = rand::thread_rng().gen();
+        *next += Duration::from_secs_f64(-(1.0 - uniform).ln() / self.rate);
+        scheduled
+    }
+
+    pub fn is_late(&self, scheduled: Instant) -> bool {
+        self.latency_limit
+            .is_some_and(|limit| scheduled.elapsed() > limit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::Throttle;
+
+    #[test]
+    fn test_schedule() {
+        let throttle = Throttle::new(1000.0, None);
+        let first = throttle.schedule();
+        let count = 10_000;
+        let last = (1..count).map(|_| throttle.schedule()).last().unwrap();
+        let mean = (last - first).as_secs_f64() / (count - 1) as f64;
+        assert!((mean - 0.001).abs() < 0.001 * 0.05, "mean interval {mean}");
+    }
+
+    #[test]
+    fn test_is_late() {
+        let now = Instant::now();
+        let late = now - Duration::from_millis(20);
+
+        let throttle = Throttle::new(100.0, Some(Duration::from_millis(10)));
+        assert!(throttle.is_late(late));
+        assert!(!throttle.is_late(now));
+        assert!(!throttle.is_late(now + Duration::from_secs(1)));
+
+        assert!(!Throttle::new(100.0, None).is_late(late));
+    }
+}
@@ -0,0 +1,25 @@
+use std::time::{Duration, Instant};
+
+pub struct Time<T> {
+    pub generator: T,
+    pub started: Instant,
+    pub duration: Duration,
+}
+
+impl<T> Time<T> {
+    pub fn new(generator: T, duration: Duration) -> Self {
+        Self {
+            generator,
+            started: Instant::now(),
+            duration,
+        }
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        self.started.elapsed() >= self.duration
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.started.elapsed())
+    }
+}
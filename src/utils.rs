@@ -1,5 +1,32 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use crc::Algorithm;
 
+/// Set when stdout carries the result document of `--output`.
+static MESSAGES_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+pub fn set_messages_to_stderr(value: bool) {
+    MESSAGES_TO_STDERR.store(value, Ordering::Relaxed);
+}
+
+pub fn messages_to_stderr() -> bool {
+    MESSAGES_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Prints a line like `println!`, to stderr when stdout is reserved for the
+/// result document.
+macro_rules! message {
+    ($($arg:tt)*) => {
+        if $crate::utils::messages_to_stderr() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use message;
+
 pub const CRC_32_TARANTOOL: Algorithm<u32> = Algorithm {
     width: 32,
     poly: 0x1edc6f41,
@@ -1,7 +1,3 @@
-_G.tpcb_like = function()
-	return true
-end
-
 -- init is a role initialization function.
 -- Can be used to create spaces, indexes, grant permissions, etc.
 local function init(opts) -- luacheck: no unused args
@@ -26,10 +22,6 @@ local function apply_config(conf, opts) -- luacheck: no unused args
 	-- if opts.is_master then
 	-- end
 
-	if not box.info.ro then
-		box.schema.func.create("tpcb_like", { language = "LUA", if_not_exists = true })
-	end
-
 	return true
 end
 